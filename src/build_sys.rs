@@ -1,14 +1,18 @@
 use crate::config::{self, KilnIngot};
 use crate::constants::PACKAGE_CONFIG_FILE;
-use crate::packaging::ingot::{IngotMetadata, Metadata};
+use crate::depgraph;
+use crate::header_gen;
+use crate::packaging::ingot::{IngotMetadata, Metadata, NativeLibKind, SharedLib};
+use crate::packaging::semver;
 use crate::{constants, utils};
 use crate::utils::Language;
 use crate::{config::Config, constants::CONFIG_FILE};
+use crate::target::{SharedLibNaming, Target};
 
 use anyhow::{anyhow, Result};
 use std::collections::{HashMap, HashSet};
 use std::{env, process};
-use std::{fs, path::Path};
+use std::{fs, path::Path, path::PathBuf};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BuildProfile  {
@@ -35,7 +39,7 @@ impl BuildProfile {
     }
 }
 
-pub fn create_project(path: &Path, lang: Language) -> Result<()> {
+pub fn create_project(path: &Path, lang: Language, license: Option<&str>) -> Result<()> {
     let toml_path = path.join(CONFIG_FILE);
     if toml_path.exists() {
         return Err(anyhow!("directory is already a Kiln project."));
@@ -48,6 +52,17 @@ pub fn create_project(path: &Path, lang: Language) -> Result<()> {
         config.project.language = "cpp".to_string();
     }
 
+    let spdx_comment = match license {
+        Some(spdx_id) => {
+            let info = crate::licenses::find(spdx_id)
+                .ok_or_else(|| anyhow!("Unknown license `{}`. See `kiln init --help` for the supported SPDX identifiers.", spdx_id))?;
+            fs::write(path.join("LICENSE"), info.text)?;
+            config.project.license = Some(info.spdx_id.to_string());
+            Some(format!("// SPDX-License-Identifier: {}\n\n", info.spdx_id))
+        }
+        None => None,
+    };
+
     let config_str = toml::to_string(&config)?;
 
     fs::write(&toml_path, config_str)?;
@@ -58,10 +73,12 @@ pub fn create_project(path: &Path, lang: Language) -> Result<()> {
     match lang {
         Language::C => {
             let starter_code = "#include <stdio.h>\n\nint main() {\n\tprintf(\"Welcome to Kiln!\\n\");\n\treturn 0;\n}";
+            let starter_code = prepend_spdx(starter_code, &spdx_comment);
             fs::write(&source_dir.join("main.c"), starter_code)?;
         }
         Language::Cpp | Language::Cuda => {
             let starter_code = "#include <iostream>\n\nint main() {\n\tstd::cout << \"Welcome to Kiln!\\n\";\n\treturn 0;\n}";
+            let starter_code = prepend_spdx(starter_code, &spdx_comment);
             fs::write(&source_dir.join("main.cpp"), starter_code)?;
         }
     }
@@ -70,6 +87,13 @@ pub fn create_project(path: &Path, lang: Language) -> Result<()> {
     Ok(())
 }
 
+fn prepend_spdx(source: &str, spdx_comment: &Option<String>) -> String {
+    match spdx_comment {
+        Some(comment) => format!("{}{}", comment, source),
+        None => source.to_string(),
+    }
+}
+
 pub fn link_sys_lib(path: &Path) -> Vec<&'static str> {
     let c_lib_mappings = [
         ("<math.h>", "-lm"),                // Math library
@@ -92,17 +116,129 @@ pub fn link_sys_lib(path: &Path) -> Vec<&'static str> {
 
     let mut libs = vec![];
 
-    // TODO: Get thing working
-    // let includes = utils::extract_include_statements(path);
-    // for (incl, link) in c_lib_mappings {
-    //     if includes.contains(&incl.to_string()) {
-    //         libs.push(link)
-    //     }
-    // }
+    let src_dir = path.join("src");
+    let Ok(entries) = fs::read_dir(&src_dir) else {
+        return libs;
+    };
+    let entry_files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+
+    let includes = depgraph::scan(&entry_files, &[], depgraph::DEFAULT_MAX_INCLUDE_DEPTH).flat;
+    for (incl, link) in c_lib_mappings {
+        if includes.contains(incl) {
+            libs.push(link);
+        }
+    }
 
     libs
 }
 
+/// The environment variable the platform's dynamic loader consults for extra
+/// shared-object search directories: `DYLD_LIBRARY_PATH` on macOS, `PATH` on
+/// Windows (there is no separate loader-path variable), and `LD_LIBRARY_PATH`
+/// everywhere else.
+pub fn dylib_env_var() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "DYLD_LIBRARY_PATH"
+    } else if cfg!(target_os = "windows") {
+        "PATH"
+    } else {
+        "LD_LIBRARY_PATH"
+    }
+}
+
+/// Creates `link` as a symlink pointing at `target` (a bare filename resolved
+/// relative to `link`'s own directory, matching `ln -s`), replacing it if it
+/// already exists. Used for the `libfoo.so`/`libfoo.so.MAJOR` compatibility
+/// links [`ProjBuilder::build_dylib`] creates next to a versioned shared
+/// library.
+fn create_symlink(target: &str, link: &Path) -> Result<()> {
+    let _ = fs::remove_file(link);
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_file(target, link)?;
+
+    Ok(())
+}
+
+/// Which dynamic memory checker `--memcheck` will wrap/instrument the binary with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemcheckBackend {
+    /// Run the existing binary under `valgrind --leak-check=full`.
+    Valgrind,
+    /// Recompile with `-fsanitize=address,undefined` instead.
+    Sanitizer,
+}
+
+/// The exit code `valgrind` is told to use when it detects an error, so it's
+/// distinguishable from the wrapped program's own exit codes.
+pub const VALGRIND_ERROR_EXIT_CODE: i32 = 99;
+
+/// Extra compiler flags for the `Sanitizer` memcheck backend.
+pub const SANITIZER_MEMCHECK_FLAGS: &[&str] = &["-fsanitize=address,undefined", "-g"];
+
+/// Picks `Valgrind` if it's on `PATH`, else falls back to `Sanitizer`.
+pub fn detect_memcheck_backend() -> MemcheckBackend {
+    let valgrind_available = process::Command::new("valgrind")
+        .arg("--version")
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+
+    if valgrind_available {
+        MemcheckBackend::Valgrind
+    } else {
+        MemcheckBackend::Sanitizer
+    }
+}
+
+/// Builds the argv to run `bin_path` (plus any passthrough `args`) under valgrind.
+pub fn wrap_with_valgrind(bin_path: &Path, args: &[String]) -> Vec<String> {
+    let mut cmd = vec![
+        "valgrind".to_string(),
+        format!("--error-exitcode={}", VALGRIND_ERROR_EXIT_CODE),
+        "--leak-check=full".to_string(),
+        bin_path.to_string_lossy().to_string(),
+    ];
+    cmd.extend(args.iter().cloned());
+    cmd
+}
+
+/// Writes a pkg-config `.pc` file for a library build into `build_dir`, so
+/// downstream projects can `pkg-config --cflags --libs <name>` against it. Only
+/// meaningful for `static_library`/`dynamic_library` build types.
+pub fn generate_pkg_config_file(
+    config: &Config,
+    project_dir: &Path,
+    build_dir: &Path,
+) -> Result<std::path::PathBuf> {
+    let include_dir = project_dir.join(&config.project.include_dir[0]);
+
+    let pc = format!(
+        "Name: {name}\n\
+         Version: {version}\n\
+         Description: {name} library built with Kiln\n\
+         Cflags: -I{include_dir}\n\
+         Libs: -L{libdir} -l{name}\n",
+        name = config.project.name,
+        version = config.project.version,
+        include_dir = include_dir.display(),
+        libdir = build_dir.display(),
+    );
+
+    let pc_path = build_dir.join(format!("{}.pc", config.project.name));
+    fs::write(&pc_path, pc)?;
+
+    Ok(pc_path)
+}
+
 pub fn validate_proj_repo(path: &Path) -> Result<()> {
     let config = path.join(CONFIG_FILE);
     if !config.exists() {
@@ -135,6 +271,7 @@ pub fn validate_proj_repo(path: &Path) -> Result<()> {
 #[derive(Debug)]
 pub struct ProjBuilder<'a> {
     config: &'a Config,
+    target: Target,
     ingots: HashSet<String>,
     pub compile_cmd: CompileCmdBuilder,
 }
@@ -149,12 +286,26 @@ pub struct CompileCmdBuilder {
     compiler: String,
     output_filename: Option<String>,
     compiler_flags: HashSet<String>,
+
+    /// Raw linker flags for explicitly-kinded `sys_libs` entries (static,
+    /// whole-archive, framework, verbatim). Unlike the sets above, this is a
+    /// `Vec`: native static-library link order is load-bearing, so entries are
+    /// appended in declaration order and never deduplicated/reordered.
+    native_libs: Vec<String>,
 }
 
 impl<'a> ProjBuilder<'a> {
+    /// Builds a `ProjBuilder` for the host platform, i.e. the behavior kiln
+    /// has always had. Use [`ProjBuilder::new_for_target`] to cross-compile.
     pub fn new(config: &'a Config) -> Self {
+        Self::new_for_target(config, Target::default_host())
+    }
+
+    pub fn new_for_target(config: &'a Config, target: Target) -> Self {
+        let compiler = config.get_compiler_path();
         let mut compile_cmd = CompileCmdBuilder {
-            compiler: config.get_compiler_path(),
+            compiler_flags: target.compiler_flags(&compiler).into_iter().collect(),
+            compiler,
             ..CompileCmdBuilder::default()
         };
 
@@ -184,7 +335,7 @@ impl<'a> ProjBuilder<'a> {
                         continue;
                     }
 
-                    if !file.file_name().to_str().unwrap().ends_with(constants::STATIC_LIB_FE) {
+                    if !file.file_name().to_str().unwrap().ends_with(target.static_lib_ext()) {
                         continue;
                     }
 
@@ -202,12 +353,13 @@ impl<'a> ProjBuilder<'a> {
 
         Self {
             config,
+            target,
             ingots: HashSet::new(),
             compile_cmd,
         }
     }
 
-    pub fn attach_ingot(&mut self, ingot: &KilnIngot) {
+    pub fn attach_ingot(&mut self, ingot: &KilnIngot) -> Result<()> {
         let path_buf = ingot.get_global_path();
         let path = path_buf.to_str()
             .unwrap()
@@ -215,7 +367,7 @@ impl<'a> ProjBuilder<'a> {
 
         if !self.ingots.insert(path.clone()) {
             // Runs if the path already exists
-            return;
+            return Ok(());
         }
 
         // Add source files and static libraries to compile comand
@@ -235,7 +387,7 @@ impl<'a> ProjBuilder<'a> {
                     if filename.ends_with(self.config.project.language_ext()) {
                         self.compile_cmd.source_files.insert(target_f);
                     }
-                    else if filename.ends_with(constants::STATIC_LIB_FE) {
+                    else if filename.ends_with(self.target.static_lib_ext()) {
                         self.compile_cmd.static_libs.insert(target_f);
                     }
                 }
@@ -255,20 +407,114 @@ impl<'a> ProjBuilder<'a> {
         let ingot_md_path = ingot_dir.join(PACKAGE_CONFIG_FILE);
         let ingot_md: IngotMetadata = IngotMetadata::from(&ingot_md_path).unwrap();
 
-        // Add syslibs to compile command
+        // Link against the exact soname `build_ingot` recorded for this
+        // dependency's shared library, rather than a bare `-l<name>` that
+        // would resolve to whichever `.so` happens to be newest on the
+        // search path. Windows has no soname; its import library is linked
+        // by name like any other library instead.
+        if let Some(shared_lib) = &ingot_md.metadata.shared_lib {
+            match &shared_lib.soname {
+                Some(soname) => self.compile_cmd.native_libs.push(format!("-l:{}", soname)),
+                None => {
+                    if let Some(implib) = &shared_lib.import_lib_filename {
+                        let name = implib.trim_end_matches(".lib");
+                        self.compile_cmd.sys_libs.insert(format!("-l{}", name));
+                    }
+                }
+            }
+        }
+
+        // `Dynamic` entries still go through pkg-config, same as before: a real
+        // system library like `gtk+-3.0` or `openssl` needs its own include paths
+        // and possibly extra defines, which a plain `-l<name>` can't express.
+        // Entries may carry a version constraint (`"openssl >= 1.1"`) — pkg-config
+        // parses that itself. The other kinds bypass pkg-config entirely and emit
+        // their linker flags directly, since they describe exact link mechanics
+        // pkg-config has no concept of (whole-archive, frameworks, verbatim names).
+        let want_static = self.config.want_static_pkg_config();
+        let mut group_static = vec![];
         for sys_lib in &ingot_md.metadata.sys_libs {
-            self.compile_cmd.sys_libs.insert(sys_lib.clone());
+            match sys_lib.kind() {
+                NativeLibKind::Dynamic => {
+                    let flags = config::resolve_pkg_config(sys_lib.name(), want_static).map_err(|e| {
+                        anyhow!(
+                            "Resolving sys_lib `{}` for ingot at {:?}: {}",
+                            sys_lib.name(),
+                            ingot_dir,
+                            e
+                        )
+                    })?;
+
+                    for flag in flags {
+                        if let Some(dir) = flag.strip_prefix("-I") {
+                            self.compile_cmd.include_dirs.insert(dir.to_string());
+                        } else if let Some(dir) = flag.strip_prefix("-L") {
+                            self.compile_cmd.dynamic_libs.insert(dir.to_string());
+                        } else if flag.starts_with("-l") {
+                            self.compile_cmd.sys_libs.insert(flag);
+                        } else {
+                            self.compile_cmd.compiler_flags.insert(flag);
+                        }
+                    }
+                }
+                NativeLibKind::Static => {
+                    // Collected rather than pushed immediately: if more than one
+                    // static lib is declared, they're wrapped in a single
+                    // `--start-group`/`--end-group` below.
+                    group_static.push(format!("-l:{}", sys_lib.name()));
+                }
+                NativeLibKind::StaticWholeArchive => {
+                    if cfg!(target_os = "macos") {
+                        self.compile_cmd
+                            .native_libs
+                            .push(format!("-Wl,-force_load,{}", sys_lib.name()));
+                    } else {
+                        self.compile_cmd.native_libs.push("-Wl,--whole-archive".to_string());
+                        self.compile_cmd.native_libs.push(format!("-l:{}", sys_lib.name()));
+                        self.compile_cmd.native_libs.push("-Wl,--no-whole-archive".to_string());
+                    }
+                }
+                NativeLibKind::Framework => {
+                    if cfg!(target_os = "macos") {
+                        self.compile_cmd.native_libs.push("-framework".to_string());
+                        self.compile_cmd.native_libs.push(sys_lib.name().to_string());
+                    } else {
+                        eprintln!(
+                            "WARNING: sys_lib `{}` is a macOS framework and has no effect on this platform",
+                            sys_lib.name()
+                        );
+                    }
+                }
+                NativeLibKind::Verbatim => {
+                    self.compile_cmd.native_libs.push(sys_lib.name().to_string());
+                }
+            }
         }
 
-        // Recursively does the same for all the other ingots. 
+        // `sys_libs` carries no explicit dependency edges, so rather than guess
+        // which statics actually depend on each other, every plain `Static` entry
+        // declared on this ingot is wrapped into one group: the linker then
+        // retries unresolved symbols across the whole set instead of failing
+        // because of declaration order alone.
+        if group_static.len() > 1 {
+            self.compile_cmd.native_libs.push("-Wl,--start-group".to_string());
+            self.compile_cmd.native_libs.extend(group_static);
+            self.compile_cmd.native_libs.push("-Wl,--end-group".to_string());
+        } else {
+            self.compile_cmd.native_libs.extend(group_static);
+        }
+
+        // Recursively does the same for all the other ingots.
         for upstream_ingot in &ingot_md.metadata.ingot_deps {
-            self.attach_ingot(upstream_ingot);
+            self.attach_ingot(upstream_ingot)?;
         }
+
+        Ok(())
     }
 
     pub fn build_exe(&mut self, build_prof: BuildProfile) -> Result<()> {
         let mut output_file = self.config.project.name.to_string();
-        output_file.push_str(constants::EXECUTABLE_FE);
+        output_file.push_str(self.target.executable_ext());
 
         let output_filepath = Path::new("build")
             .join(build_prof.to_str(false))
@@ -300,14 +546,14 @@ impl<'a> ProjBuilder<'a> {
         Ok(())
     }
 
-    pub fn build_ingot(&self) {
+    pub fn build_ingot(&mut self) {
         let ingot_dir = Path::new("build").join("ingot");
         if fs::exists(&ingot_dir).unwrap() {
             fs::remove_dir_all(&ingot_dir).unwrap();
         }
 
         fs::create_dir_all(&ingot_dir).unwrap();
-        
+
         for src_file in &self.compile_cmd.source_files {
             let src_filename = utils::extract_filename(src_file);
             fs::copy(&src_file, ingot_dir.join(src_filename)).unwrap();
@@ -332,6 +578,42 @@ impl<'a> ProjBuilder<'a> {
             }
         }
 
+        let exported_headers = self.config.project.public_headers.clone().unwrap_or_default();
+        if !exported_headers.is_empty() {
+            let umbrella = header_gen::gen_umbrella_header(
+                &self.config.project.name,
+                &ingot_dir,
+                &exported_headers,
+            ).unwrap();
+            let umbrella_name = format!("{}.h", self.config.project.name);
+            fs::write(ingot_dir.join(umbrella_name), umbrella).unwrap();
+        }
+
+        // A published ingot always ships release artifacts (unlike
+        // `packaging::publish::package_ingot`, which tars the project's sources).
+        let shared_lib = if self.config.project.build_type.contains(&config::BuildType::dynamic_library) {
+            let (dylib_dir, naming) = self.build_dylib(BuildProfile::Release).unwrap();
+
+            fs::copy(
+                dylib_dir.join(&naming.versioned_filename),
+                ingot_dir.join(&naming.versioned_filename),
+            ).unwrap();
+            for (link_name, target_name) in &naming.symlinks {
+                create_symlink(target_name, &ingot_dir.join(link_name)).unwrap();
+            }
+            if let Some(implib) = &naming.import_lib_filename {
+                fs::copy(dylib_dir.join(implib), ingot_dir.join(implib)).unwrap();
+            }
+
+            Some(SharedLib {
+                versioned_filename: naming.versioned_filename,
+                soname: naming.soname,
+                import_lib_filename: naming.import_lib_filename,
+            })
+        } else {
+            None
+        };
+
         let mut ingot_deps = vec![];
         if let Some(v) = &self.config.dependency {
             ingot_deps = v.clone();
@@ -342,6 +624,9 @@ impl<'a> ProjBuilder<'a> {
                 ingot_deps,
                 sys_libs: vec![], // TODO -> Fill this out properly
                 staticlib_support: false,
+                dynamiclib_support: shared_lib.is_some(),
+                shared_lib,
+                exported_headers,
                 source_support: true,
             }
         };
@@ -351,6 +636,51 @@ impl<'a> ProjBuilder<'a> {
             .unwrap();
     }
 
+    /// Compiles the project as a shared library named and versioned per
+    /// `self.target.shared_lib_naming` (derived from the ingot's own
+    /// `project.version`), then recreates its compatibility symlinks next to
+    /// it. Returns the directory the artifacts were written to and the
+    /// naming kiln used, so callers know exactly which files to pick up.
+    pub fn build_dylib(&mut self, build_prof: BuildProfile) -> Result<(PathBuf, SharedLibNaming)> {
+        let version = semver::Version::parse(&self.config.project.version).ok_or_else(|| {
+            anyhow!("Invalid project version `{}`", self.config.project.version)
+        })?;
+        let naming = self.target.shared_lib_naming(&self.config.project.name, &version);
+
+        let output_dir = Path::new("build").join(build_prof.to_str(false));
+        fs::create_dir_all(&output_dir)?;
+
+        let output_filepath = output_dir.join(&naming.versioned_filename);
+        self.compile_cmd.output_filename = Some(output_filepath.to_str().unwrap().to_string());
+        self.compile_cmd.compiler_flags.extend(naming.link_flags.iter().cloned());
+
+        let (shell, flag) = if cfg!(target_os = "windows") {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+
+        let compile_cmd = self.compile_cmd.generate_compile_cmd(config::BuildType::dynamic_library).join(" ");
+
+        let cmd = process::Command::new(shell)
+            .arg(flag)
+            .arg(&compile_cmd)
+            .stdout(process::Stdio::inherit())
+            .stderr(process::Stdio::inherit())
+            .stdin(process::Stdio::inherit())
+            .output()?;
+
+        if !cmd.status.success() {
+            process::exit(1);
+        }
+
+        for (link_name, target_name) in &naming.symlinks {
+            create_symlink(target_name, &output_dir.join(link_name))?;
+        }
+
+        Ok((output_dir, naming))
+    }
+
 }
 
 impl CompileCmdBuilder {
@@ -381,6 +711,14 @@ impl CompileCmdBuilder {
         for sys_lib in &self.sys_libs {
             compile_cmd.push(format!("\"{}\"", sys_lib));
         }
+        // Declaration order preserved: native static-library link order is
+        // load-bearing (group/whole-archive flags must bracket the right libs).
+        for native_lib in &self.native_libs {
+            compile_cmd.push(format!("\"{}\"", native_lib));
+        }
+        for compiler_flag in &self.compiler_flags {
+            compile_cmd.push(format!("\"{}\"", compiler_flag));
+        }
 
         match build_type {
             config::BuildType::exe => {
@@ -390,7 +728,9 @@ impl CompileCmdBuilder {
                 // Already taken care of above
             }
             config::BuildType::dynamic_library => {
-                unimplemented!();
+                // Already taken care of above: the `-shared` flag up top and
+                // any soname/install_name/import-lib flags `build_dylib`
+                // pushed into `compiler_flags` before calling this.
             }
             config::BuildType::ingot => {
                 unreachable!("You should not be calling this function to build an ingot (if you are a user, please file a github issue)");
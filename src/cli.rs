@@ -1,7 +1,63 @@
+use crate::config::Config;
 use crate::utils::{self, Language};
 
 use clap::{Parser, Subcommand};
 
+/// The subset of subcommand names clap already knows about, so an alias can never
+/// shadow a built-in verb.
+const BUILTIN_COMMANDS: &[&str] = &[
+    "init", "new", "gen-headers", "add", "purge-global-installs", "package", "build", "run",
+    "build-trace", "test", "local-dev",
+];
+
+/// Caps alias recursion so a cycle like `a = "a"` errors instead of looping forever.
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Expands `raw_args[1]` against `config`'s `[alias]` table, splicing the alias'
+/// expansion (split on whitespace) in place of the alias name and keeping any
+/// arguments the user passed after it. Returns `raw_args` unchanged if there's no
+/// alias table, too few arguments, or the first token isn't an alias.
+pub fn expand_aliases(config: Option<&Config>, raw_args: &[String]) -> Result<Vec<String>, String> {
+    let Some(config) = config else {
+        return Ok(raw_args.to_vec());
+    };
+    let Some(aliases) = &config.alias else {
+        return Ok(raw_args.to_vec());
+    };
+    if raw_args.len() < 2 {
+        return Ok(raw_args.to_vec());
+    }
+
+    let mut args = raw_args.to_vec();
+    let mut depth = 0;
+
+    loop {
+        let verb = args[1].as_str();
+        if BUILTIN_COMMANDS.contains(&verb) {
+            break;
+        }
+        let Some(expansion) = aliases.get(verb) else {
+            break;
+        };
+
+        depth += 1;
+        if depth > MAX_ALIAS_DEPTH {
+            return Err(format!(
+                "Alias `{}` did not resolve to a built-in command within {} expansions (cycle?)",
+                raw_args[1], MAX_ALIAS_DEPTH
+            ));
+        }
+
+        let expanded: Vec<String> = expansion.split_whitespace().map(str::to_string).collect();
+        let mut new_args = vec![args[0].clone()];
+        new_args.extend(expanded);
+        new_args.extend_from_slice(&args[2..]);
+        args = new_args;
+    }
+
+    Ok(args)
+}
+
 #[derive(Parser, Debug)]
 #[command(name = "Kiln")]
 #[command(version = "0.1.6")]
@@ -16,40 +72,108 @@ pub enum Commands {
     Init {
         #[arg(value_enum, long, default_value = "c")]
         language: utils::Language,
+
+        /// SPDX identifier of a license to bootstrap with (e.g. `MIT`, `Apache-2.0`).
+        /// Writes a `LICENSE` file and records the choice in `Kiln.toml`.
+        #[arg(long)]
+        license: Option<String>,
     },
     New {
         proj_name: String,
 
         #[arg(value_enum, long, default_value = "c")]
         language: utils::Language,
+
+        /// SPDX identifier of a license to bootstrap with (e.g. `MIT`, `Apache-2.0`).
+        /// Writes a `LICENSE` file and records the choice in `Kiln.toml`.
+        #[arg(long)]
+        license: Option<String>,
     },
     GenHeaders {
         #[arg()]
-        args: Option<Vec<String>>
+        args: Option<Vec<String>>,
+
+        /// Also emit a merged public header (`<project name>.h`) that `#include`s
+        /// every generated per-file header, for use as a single library entry point.
+        #[arg(long)]
+        umbrella: bool,
     },
     Add {
         dep_uri: String,
+
+        /// Allow transitive dependencies that declare a `[scripts]` pre-build or
+        /// post-fetch hook. By default Kiln refuses to resolve such a dependency
+        /// unless it was requested directly, since a deep dependency shouldn't be
+        /// able to run arbitrary code on a routine `kiln add`.
+        #[arg(long)]
+        allow_build_scripts: bool,
+
+        /// Resolve entirely from the global package cache, never touching the
+        /// network. Fails with a clear error naming the first package/version
+        /// that would need to be fetched.
+        #[arg(long)]
+        offline: bool,
     },
     PurgeGlobalInstalls,
 
+    /// Builds a verified, reproducible ingot tarball for distribution.
+    Package {
+        /// Print the file set that would be packaged without writing the archive.
+        #[arg(long)]
+        list: bool,
+    },
+
     // Clap doesn't provide any way to structure the syntax to be `kiln run --profile
     // So, we'll have to parse these manually.
     Build {
         #[arg(default_value_t = String::from("--debug"))]
         profile: String,
+
+        /// Cross-compile for this target triple (e.g. `aarch64-unknown-linux-gnu`)
+        /// instead of the host kiln itself was built on.
+        #[arg(long)]
+        target: Option<String>,
     },
     Run {
         profile: String,
         args: Vec<String>,
+
+        /// Run the binary under a memory checker (valgrind if available, else an
+        /// AddressSanitizer/UBSan-instrumented rebuild).
+        memcheck: bool,
+
+        /// Cross-compile for this target triple (e.g. `aarch64-unknown-linux-gnu`)
+        /// instead of the host kiln itself was built on.
+        target: Option<String>,
     },
 
     BuildTrace {
         #[arg(default_value_t = String::from("--debug"))]
         profile: String,
+
+        /// Cross-compile for this target triple (e.g. `aarch64-unknown-linux-gnu`)
+        /// instead of the host kiln itself was built on.
+        #[arg(long)]
+        target: Option<String>,
     },
 
     Test {
-        tests: Option<Vec<String>>
+        tests: Option<Vec<String>>,
+
+        /// Overwrite each test's `kiln-expected-stdout` file with the observed
+        /// output instead of diffing against it.
+        #[arg(long)]
+        bless: bool,
+
+        /// Run each test binary under a memory checker (valgrind if available,
+        /// else an AddressSanitizer/UBSan-instrumented rebuild).
+        #[arg(long)]
+        memcheck: bool,
+
+        /// How many tests to compile and run concurrently. Defaults to the
+        /// system's available parallelism.
+        #[arg(long)]
+        jobs: Option<usize>,
     },
     LocalDev {
         #[command(subcommand)]
@@ -58,17 +182,27 @@ pub enum Commands {
 }
 
 impl Commands {
-    pub fn new(variant: &str, profile: &str, args: Vec<String>) -> Self {
+    pub fn new(
+        variant: &str,
+        profile: &str,
+        args: Vec<String>,
+        memcheck: bool,
+        target: Option<String>,
+    ) -> Self {
         match variant {
             "build" => Self::Build {
                 profile: profile.to_string(),
+                target,
             },
             "run" => Self::Run {
                 profile: profile.to_string(),
                 args,
+                memcheck,
+                target,
             },
-            "build-trace" => Self::BuildTrace { 
-                profile: profile.to_string() 
+            "build-trace" => Self::BuildTrace {
+                profile: profile.to_string(),
+                target,
             },
             _ => panic!("Parameter `variant` must be one of 'build' or 'run'"),
         }
@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
@@ -7,6 +7,7 @@ use std::{
 use toml;
 
 use crate::constants::{CONFIG_FILE, PACKAGE_DIR};
+use crate::kiln_error::{KilnError, KilnResult};
 use crate::package_manager;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +15,64 @@ pub struct Config {
     pub project: Project,
     pub build_options: BuildOptions,
     pub dependency: Option<Vec<KilnIngot>>,
+
+    /// User-defined command aliases, e.g. `br = "build --release"`.
+    pub alias: Option<std::collections::HashMap<String, String>>,
+
+    /// Per-rule overrides for the static analyzer, keyed by rule id
+    /// (e.g. `"unsafe-function"`, `"format-string"`, `"fixed-buffer"`).
+    pub static_analysis: Option<std::collections::HashMap<String, RuleConfig>>,
+
+    /// Shell commands this package asks to run around its own build/fetch.
+    pub scripts: Option<Scripts>,
+}
+
+/// Hooks a package can declare in its `[scripts]` table. Kiln does not run
+/// these itself; `package_manager::resolve_adding_package` refuses a
+/// transitive dependency that declares either one unless
+/// `allow_build_scripts` is set.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Scripts {
+    /// Pre-build command (before the package's sources are compiled).
+    pub pre_build: Option<String>,
+    /// Post-fetch command (after the package's tarball is fetched and unpacked).
+    pub post_fetch: Option<String>,
+}
+
+/// One rule's enable/severity override within the `[static_analysis]` table.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RuleConfig {
+    pub enabled: Option<bool>,
+    pub severity: Option<Severity>,
+}
+
+/// How seriously a static analysis finding should be treated. `Error`-severity
+/// warnings cause `check_files` to fail the build.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Where a dependency's source actually comes from.
+///
+/// `Git` is checked out at a deterministic `rev` (branch, tag, or commit SHA) rather
+/// than a loose version string, and `Local` points at an on-disk ingot with no fetch
+/// step at all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DepSource {
+    Git {
+        git: String,
+        rev: String,
+        #[serde(default)]
+        subpath: Option<String>,
+    },
+    Local {
+        path: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +84,15 @@ pub struct Project {
     pub src_dir: Vec<String>,
     pub include_dir: Vec<String>,
     pub staticlib_dir: Option<Vec<String>>,
+
+    /// Headers (by filename, resolved against `include_dir`) that make up this
+    /// ingot's public C API. The ingot build (`ProjBuilder::build_ingot`) only
+    /// folds these into the generated umbrella header, not every header under
+    /// `include_dir`.
+    pub public_headers: Option<Vec<String>>,
+
+    /// SPDX identifier of the license chosen at `kiln init`/`kiln new` time, e.g. `"MIT"`.
+    pub license: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,12 +103,25 @@ pub struct BuildOptions {
     compiler_path: Option<String>,
     standard: Option<String>,
     kiln_static_analysis: Option<bool>,
+
+    /// Names of system libraries (as known to `pkg-config`, e.g. `"openssl"`) to
+    /// resolve into `-I`/`-L`/`-l` flags at build time.
+    pkg_config: Option<Vec<String>>,
+
+    /// When building a `static_library`/`dynamic_library` target, also emit a
+    /// pkg-config `.pc` file describing it.
+    emit_pc_file: Option<bool>,
+
+    /// Pass `--static` when resolving `pkg_config` (and an ingot's own
+    /// `sys_libs`) entries, for projects that want pkg-config's static-linking
+    /// flag set rather than its default dynamic one.
+    pkg_config_static: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KilnIngot {
-    pub uri: String,
-    pub version: String,
+    #[serde(flatten)]
+    pub source: DepSource,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -68,6 +149,8 @@ impl Config {
             src_dir: vec!["src".to_string()],
             include_dir: vec!["include".to_string()],
             staticlib_dir: None,
+            public_headers: None,
+            license: None,
         };
 
         let build_options = BuildOptions::default();
@@ -76,23 +159,46 @@ impl Config {
             project,
             build_options,
             dependency: None,
+            alias: None,
+            static_analysis: None,
+            scripts: None,
         }
     }
 
-    pub fn from(path: &Path) -> Result<Self> {
-        let toml_str = fs::read_to_string(path)?;
+    pub fn from(path: &Path) -> KilnResult<Self> {
+        let toml_str = fs::read_to_string(path).map_err(|e| KilnError::from_io(path, e))?;
 
-        let config: Config = toml::from_str(&toml_str)?;
+        let config: Config = match FileFormat::from_path(path) {
+            Some(format) => format.parse(&toml_str, path)?,
+            // Extension missing or unrecognized -- try each format in turn
+            // and keep whichever one parses, surfacing the last format's
+            // error if none do.
+            None => {
+                let mut last_err = None;
+                let mut parsed = None;
+                for format in FileFormat::ALL {
+                    match format.parse(&toml_str, path) {
+                        Ok(config) => {
+                            parsed = Some(config);
+                            break;
+                        }
+                        Err(e) => last_err = Some(e),
+                    }
+                }
+                match parsed {
+                    Some(config) => config,
+                    None => return Err(last_err.unwrap()),
+                }
+            }
+        };
 
         let build_types = &config.project.build_type;
 
         if build_types.len() == 0 {
-            return Err(anyhow!("Project must have a build type"));
+            crate::bail!(Config, "Project must have a build type");
         }
         if build_types.contains(&BuildType::exe) && build_types.len() > 1 {
-            return Err(anyhow!(
-                "Project cannot be executable in addition to other types"
-            ));
+            crate::bail!(Config, "Project cannot be executable in addition to other types");
         }
 
         Ok(config)
@@ -125,8 +231,46 @@ impl Config {
         }
     }
 
+    /// Whether `handle_build` should emit a pkg-config `.pc` file alongside a
+    /// library build.
+    pub fn emit_pc_file(&self) -> bool {
+        self.build_options.emit_pc_file.unwrap_or(false)
+    }
+
+    /// Whether the static analyzer should run at all: honors the legacy
+    /// `build_options.kiln_static_analysis` switch, and additionally treats a
+    /// `[static_analysis]` table with every rule disabled as "off".
     pub fn kiln_static_analysis(&self) -> bool {
-        self.build_options.kiln_static_analysis.unwrap_or(true)
+        if !self.build_options.kiln_static_analysis.unwrap_or(true) {
+            return false;
+        }
+
+        match &self.static_analysis {
+            Some(rules) if !rules.is_empty() => {
+                rules.values().any(|r| r.enabled.unwrap_or(true))
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether `rule` is enabled, defaulting to `default` if `kiln.toml` doesn't
+    /// mention it.
+    pub fn rule_enabled(&self, rule: &str, default: bool) -> bool {
+        self.static_analysis
+            .as_ref()
+            .and_then(|rules| rules.get(rule))
+            .and_then(|r| r.enabled)
+            .unwrap_or(default)
+    }
+
+    /// `rule`'s configured severity, defaulting to `default` if `kiln.toml`
+    /// doesn't override it.
+    pub fn rule_severity(&self, rule: &str, default: Severity) -> Severity {
+        self.static_analysis
+            .as_ref()
+            .and_then(|rules| rules.get(rule))
+            .and_then(|r| r.severity)
+            .unwrap_or(default)
     }
 
     pub fn get_standard(&self) -> Option<String> {
@@ -141,9 +285,113 @@ impl Config {
             comp_flags = self.build_options.release_flags.clone()
         }
         comp_flags.extend_from_slice(&self.build_options.shared_flags);
+        comp_flags.extend(self.get_pkg_config_flags().unwrap_or_default());
 
         comp_flags
     }
+
+    /// Whether `pkg-config` resolution (for `build_options.pkg_config` and an
+    /// ingot's own `sys_libs`) should pass `--static`.
+    pub fn want_static_pkg_config(&self) -> bool {
+        self.build_options.pkg_config_static.unwrap_or(false)
+    }
+
+    /// Resolves every entry in `build_options.pkg_config` into compiler/linker flags
+    /// via `pkg-config --cflags --libs <name>`, deduping against flags already present.
+    pub fn get_pkg_config_flags(&self) -> Result<Vec<String>> {
+        let Some(libs) = &self.build_options.pkg_config else {
+            return Ok(vec![]);
+        };
+
+        let want_static = self.want_static_pkg_config();
+        let mut seen = std::collections::HashSet::new();
+        let mut flags = vec![];
+
+        for lib in libs {
+            for flag in resolve_pkg_config(lib, want_static)? {
+                if seen.insert(flag.clone()) {
+                    flags.push(flag);
+                }
+            }
+        }
+
+        Ok(flags)
+    }
+}
+
+/// A serialization format [`Config`] can be loaded from, picked from the
+/// config path's extension. When the extension is missing or unrecognized,
+/// `Config::from` falls back to trying each format in turn (same as
+/// `config-rs`'s file module) and keeps whichever one parses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl FileFormat {
+    const ALL: [FileFormat; 3] = [FileFormat::Toml, FileFormat::Json, FileFormat::Yaml];
+
+    /// File extensions (without the leading `.`) this format is recognized by.
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            FileFormat::Toml => &["toml"],
+            FileFormat::Json => &["json"],
+            FileFormat::Yaml => &["yaml", "yml"],
+        }
+    }
+
+    /// The format `path`'s extension names, or `None` if it's missing or
+    /// doesn't match any format's `extensions()`.
+    fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension().and_then(|e| e.to_str())?;
+        Self::ALL.into_iter().find(|f| f.extensions().contains(&ext))
+    }
+
+    /// Parses `text` (the already-read contents of `path`) as this format.
+    fn parse(self, text: &str, path: &Path) -> KilnResult<Config> {
+        match self {
+            FileFormat::Toml => {
+                toml::from_str(text).map_err(|e| KilnError::toml_parse(path, text, e))
+            }
+            FileFormat::Json => serde_json::from_str(text).map_err(|e| KilnError::json_parse(path, e)),
+            FileFormat::Yaml => serde_yaml::from_str(text).map_err(|e| KilnError::yaml_parse(path, e)),
+        }
+    }
+}
+
+/// Runs `pkg-config --cflags --libs [--static] <spec>` for one pkg-config module
+/// spec and returns the resulting flag tokens (`-I`, `-L`, `-l`, `-D`, and anything
+/// else pkg-config hands back, e.g. `-pthread`). `spec` may be a bare module name
+/// (`"openssl"`) or carry a version constraint (`"openssl >= 1.1"`) — pkg-config
+/// parses that comparison itself, so it's passed through to the binary verbatim
+/// rather than split apart here. `PKG_CONFIG_PATH` and `PKG_CONFIG_SYSROOT_DIR`
+/// are honored for free: pkg-config reads them from its own environment, which
+/// `Command` inherits from this process by default.
+pub fn resolve_pkg_config(spec: &str, want_static: bool) -> Result<Vec<String>> {
+    let mut args = vec!["--cflags", "--libs"];
+    if want_static {
+        args.push("--static");
+    }
+    args.push(spec);
+
+    let output = process::Command::new("pkg-config")
+        .args(&args)
+        .output()
+        .context("Failed to run pkg-config (is it installed?)")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "pkg-config could not find `{}`:\n{}",
+            spec,
+            stderr.trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.split_whitespace().map(str::to_string).collect())
 }
 
 impl Default for BuildOptions {
@@ -163,32 +411,94 @@ impl Default for BuildOptions {
             shared_flags,
             compiler_path: None,
             kiln_static_analysis: None,
+            pkg_config: None,
+            emit_pc_file: None,
+            pkg_config_static: None,
         }
     }
 }
 
 impl KilnIngot {
-    pub fn new(owner: &str, repo_name: &str, version: &str) -> Self {
+    /// Builds a pinned `Git` dependency pointing at a GitHub repo (the historical default).
+    pub fn new(owner: &str, repo_name: &str, rev: &str) -> Self {
+        Self::new_with_host("github.com", owner, repo_name, rev)
+    }
+
+    /// Builds a pinned `Git` dependency pointing at `owner/repo_name` on `host`
+    /// (e.g. `"github.com"`, `"gitlab.com"`, or a generic HTTP registry host).
+    pub fn new_with_host(host: &str, owner: &str, repo_name: &str, rev: &str) -> Self {
         KilnIngot {
-            uri: format!("https://github.com/{}/{}.git", owner, repo_name),
-            version: version.to_string(),
+            source: DepSource::Git {
+                git: format!("https://{}/{}/{}.git", host, owner, repo_name),
+                rev: rev.to_string(),
+                subpath: None,
+            },
+        }
+    }
+
+    pub fn local(path: &str) -> Self {
+        KilnIngot {
+            source: DepSource::Local {
+                path: path.to_string(),
+            },
+        }
+    }
+
+    /// The git remote, or `None` for a `Local` dependency.
+    pub fn remote(&self) -> Option<&str> {
+        match &self.source {
+            DepSource::Git { git, .. } => Some(git),
+            DepSource::Local { .. } => None,
+        }
+    }
+
+    /// The host a `Git` dependency is fetched from (e.g. `"github.com"`); `"local"`
+    /// for a `Local` dependency, which has no remote to speak of.
+    pub fn host(&self) -> &str {
+        match &self.source {
+            DepSource::Git { git, .. } => package_manager::parse_repo_uri(git).unwrap().0,
+            DepSource::Local { .. } => "local",
         }
     }
 
     pub fn owner(&self) -> &str {
-        let (owner, _repo) = package_manager::parse_github_uri(&self.uri).unwrap();
-        owner
+        match &self.source {
+            DepSource::Git { git, .. } => package_manager::parse_repo_uri(git).unwrap().1,
+            DepSource::Local { path } => path,
+        }
     }
-    
+
     pub fn repo_name(&self) -> &str {
-        let (_owner, repo) = package_manager::parse_github_uri(&self.uri).unwrap();
-        repo
+        match &self.source {
+            DepSource::Git { git, .. } => package_manager::parse_repo_uri(git).unwrap().2,
+            DepSource::Local { path } => path,
+        }
     }
 
-    pub fn get_global_path(&self) -> PathBuf {
-        let (owner, repo) = package_manager::parse_github_uri(&self.uri).unwrap();
+    /// The resolved revision (branch, tag, or commit SHA) for a `Git` dependency.
+    pub fn rev(&self) -> Option<&str> {
+        match &self.source {
+            DepSource::Git { rev, .. } => Some(rev),
+            DepSource::Local { .. } => None,
+        }
+    }
 
-        (*PACKAGE_DIR).join(owner).join(repo).join(&self.version)
+    pub fn get_global_path(&self) -> PathBuf {
+        match &self.source {
+            DepSource::Git { subpath, .. } => {
+                let (host, owner, repo) = package_manager::parse_repo_uri(self.remote().unwrap()).unwrap();
+                let base = (*PACKAGE_DIR)
+                    .join(host)
+                    .join(owner)
+                    .join(repo)
+                    .join(self.rev().unwrap());
+                match subpath {
+                    Some(sp) => base.join(sp),
+                    None => base,
+                }
+            }
+            DepSource::Local { path } => PathBuf::from(path),
+        }
     }
 
     pub fn get_kiln_cfg(&self) -> Result<Option<Config>> {
@@ -202,13 +512,15 @@ impl KilnIngot {
     }
 
     pub fn include_dir(&self) -> PathBuf {
-        let p = self.get_global_path();
-        p.join("build").join("ingot")
+        match &self.source {
+            DepSource::Git { .. } => self.get_global_path().join("build").join("ingot"),
+            // A local ingot has no fetch/build-copy step; use it in place.
+            DepSource::Local { .. } => self.get_global_path(),
+        }
     }
 
     pub fn get_source_dir(&self) -> PathBuf {
-        let p = self.get_global_path();
-        p.join("build").join("ingot")
+        self.include_dir()
     }
 
     /// Adds a dependency if it doesn't already exist
@@ -10,6 +10,10 @@ pub const CONFIG_FILE: &str = "Kiln.toml";
 pub const DEV_ENV_CFG_FILE: &str = "kiln-dev-env-config.toml";
 pub const PACKAGE_CONFIG_FILE: &str = "ingot.toml";
 
+/// Lockfile recording the exact tag/tarball/integrity used to resolve each
+/// dependency, so repeat installs are reproducible and tamper-evident.
+pub const LOCKFILE: &str = "Kiln.lock";
+
 pub static DATA_DIR: Lazy<PathBuf> = Lazy::new(|| {
     let paths = [
         ("linux", "/usr/share/kiln/", "~/.local/share/kiln/"),
@@ -49,6 +53,32 @@ pub static PACKAGE_DIR: Lazy<PathBuf> = Lazy::new(|| {
     data_dir.join("packages")
 });
 
+/// On-disk cache of each repo's resolved tag list, so a large transitive
+/// resolution doesn't re-request the same repo's tags over and over.
+pub static TAG_CACHE_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    let data_dir = (*DATA_DIR).clone();
+    data_dir.join("tag_cache")
+});
+
+/// Content-addressed store backing [`PACKAGE_DIR`]'s per-tag directories: the
+/// unpacked tree for a given tarball lives once under its content hash here,
+/// and the `host/owner/repo/tag` path is just a link to it, so two tags (or
+/// two repos) that happen to vendor byte-identical releases only pay the
+/// download and unpack cost once.
+pub static PACKAGE_STORE_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    let data_dir = (*DATA_DIR).clone();
+    data_dir.join("package_store")
+});
+
+/// Cache of the per-file content hashes [`crate::depgraph`] records for each
+/// project (the include graph itself is rescanned, not cached), keyed by a
+/// hash of the project's path so unrelated projects on the same machine
+/// can't collide.
+pub static DEPGRAPH_CACHE_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    let data_dir = (*DATA_DIR).clone();
+    data_dir.join("depgraph_cache")
+});
+
 pub static SEPARATOR: Lazy<ColoredString> = Lazy::new(|| {
     "✦ ═════════════════════════════════ ⚔ ═════════════════════════════════ ✦"
         .to_string()
@@ -56,47 +86,3 @@ pub static SEPARATOR: Lazy<ColoredString> = Lazy::new(|| {
         .bold()
 });
 
-/// File extension for the static library
-pub const STATIC_LIB_FE: &'static str = const {
-    #[cfg(target_os = "linux")] {
-        ".a"
-    }
-
-    #[cfg(target_os = "macos")] {
-        ".a"
-    }
-
-    #[cfg(target_os = "windows")] {
-        ".lib"
-    }
-};
-
-/// File extension for the dynamic library
-pub const DYNAMIC_LIB_FE: &'static str = const {
-    #[cfg(target_os = "linux")] {
-        ".so"
-    }
-
-    #[cfg(target_os = "macos")] {
-        ".dylib"
-    }
-
-    #[cfg(target_os = "windows")] {
-        ".dll"
-    }
-};
-
-/// File extension for an executable file
-pub const EXECUTABLE_FE: &'static str = const {
-    #[cfg(target_os = "linux")] {
-        ""
-    }
-    
-    #[cfg(target_os = "macos")] {
-        ""
-    }
-
-    #[cfg(target_os = "windows")] {
-        ".exe"
-    }
-};
\ No newline at end of file
@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::DEPGRAPH_CACHE_DIR;
+use crate::header_gen::lexer_c::{self, Token};
+use crate::packaging::lockfile::content_address;
+
+/// One `#include` a scanned file follows: its raw spelling (`<math.h>` or
+/// `"local.h"`, used as-is by callers like [`crate::build_sys::link_sys_lib`]'s
+/// header-to-library table) and, if it resolved to a file kiln can see on disk,
+/// that path.
+#[derive(Debug, Clone)]
+struct RawInclude {
+    raw: String,
+    resolved: Option<PathBuf>,
+}
+
+/// The result of [`scan`]: the flat set of raw include spellings seen across
+/// every file visited (for callers that only care "was X included anywhere"),
+/// plus the file→file edges that were actually resolved to disk (for
+/// incremental-rebuild dependency tracking).
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub flat: HashSet<String>,
+    pub graph: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl ScanResult {
+    /// Every file transitively reachable from `entry` via `#include`,
+    /// including `entry` itself (whether or not it was one of the files
+    /// `scan` actually visited).
+    pub fn transitive_deps(&self, entry: &Path) -> HashSet<PathBuf> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![entry.to_path_buf()];
+
+        while let Some(file) = stack.pop() {
+            if !seen.insert(file.clone()) {
+                continue;
+            }
+            if let Some(deps) = self.graph.get(&file) {
+                stack.extend(deps.iter().cloned());
+            }
+        }
+
+        seen
+    }
+}
+
+/// How deep a chain of `#include`s [`scan`] will follow before giving up on
+/// a branch — the same safety valve a real C preprocessor needs against a
+/// misconfigured search path (e.g. a directory that includes itself) turning
+/// a typo into unbounded work.
+pub const DEFAULT_MAX_INCLUDE_DEPTH: usize = 200;
+
+/// Scans every file in `entry_files`, following their `#include` directives
+/// (and transitively, whatever those pull in, down to `max_depth` levels)
+/// via the same lexer `header_gen` uses, rather than a line-prefix match:
+/// both `<...>` system and `"..."` local includes are followed, local ones
+/// are resolved relative to the including file before falling back to
+/// `include_dirs`, and an include guarded by a false `#if 0`/`#ifdef`-of-an-
+/// undefined-macro branch is skipped so disabled code can't create a phantom
+/// dependency. A file beyond `max_depth` is still recorded (so its includer
+/// isn't left with a dangling edge) but its own includes aren't followed
+/// further. An `#include` cycle can't make this loop forever — each file is
+/// only ever queued once — but doesn't get reported as such either; call
+/// [`detect_cycles`] on the result if that matters to the caller.
+pub fn scan(entry_files: &[PathBuf], include_dirs: &[PathBuf], max_depth: usize) -> ScanResult {
+    let mut result = ScanResult::default();
+    let mut queue: Vec<(PathBuf, usize)> = entry_files.iter().cloned().map(|f| (f, 0)).collect();
+    let mut queued: HashSet<PathBuf> = entry_files.iter().cloned().collect();
+
+    while let Some((file, depth)) = queue.pop() {
+        if result.graph.contains_key(&file) {
+            continue;
+        }
+
+        let Ok(text) = fs::read_to_string(&file) else {
+            result.graph.insert(file, vec![]);
+            continue;
+        };
+
+        if depth >= max_depth {
+            result.graph.insert(file, vec![]);
+            continue;
+        }
+
+        let includes = scan_file(&file, &text, include_dirs);
+        let mut resolved_deps = vec![];
+        for inc in includes {
+            result.flat.insert(inc.raw);
+            if let Some(resolved) = inc.resolved {
+                if queued.insert(resolved.clone()) {
+                    queue.push((resolved.clone(), depth + 1));
+                }
+                resolved_deps.push(resolved);
+            }
+        }
+
+        result.graph.insert(file, resolved_deps);
+    }
+
+    result
+}
+
+/// Every `#include` cycle reachable from `entry_files` in `result`'s graph —
+/// a file that, directly or transitively, ends up `#include`ing itself —
+/// reported as the chain of paths that closes the loop, so a caller can
+/// surface something more useful than kiln quietly never revisiting a file
+/// twice. `result` should come from a [`scan`] call that covered
+/// `entry_files`.
+pub fn detect_cycles(result: &ScanResult, entry_files: &[PathBuf]) -> Vec<Vec<PathBuf>> {
+    let mut cycles = vec![];
+    let mut visited = HashSet::new();
+
+    for entry in entry_files {
+        let mut chain = vec![];
+        find_cycles(entry, result, &mut chain, &mut visited, &mut cycles);
+    }
+
+    cycles
+}
+
+fn find_cycles(
+    file: &Path,
+    result: &ScanResult,
+    chain: &mut Vec<PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+    cycles: &mut Vec<Vec<PathBuf>>,
+) {
+    if let Some(start) = chain.iter().position(|f| f == file) {
+        cycles.push(chain[start..].iter().cloned().chain([file.to_path_buf()]).collect());
+        return;
+    }
+    if !visited.insert(file.to_path_buf()) {
+        return;
+    }
+
+    chain.push(file.to_path_buf());
+    if let Some(deps) = result.graph.get(file) {
+        for dep in deps {
+            find_cycles(dep, result, chain, visited, cycles);
+        }
+    }
+    chain.pop();
+}
+
+/// A file's own `#define`s and UDTs, rendered via [`Token::tokens_to_string`]
+/// rather than kept as borrowed token slices — merging across many files'
+/// source buffers has no single lifetime to borrow from, so [`FileDefs`]
+/// trades that borrow for an owned copy instead.
+#[derive(Debug, Default, Clone)]
+pub struct FileDefs {
+    pub defines: HashMap<String, String>,
+    pub udts: HashMap<String, String>,
+}
+
+/// Every `#define` and user-defined type visible by the time `entry` is
+/// reached: its own, plus everything transitively `#include`d before it, per
+/// `result`'s graph. Re-reads and re-tokenizes each file in
+/// [`ScanResult::transitive_deps`] — the same "go back to disk rather than
+/// try to cache an AST" choice [`stale_entries`]/[`update_cache`] make — so
+/// this merges the full transitive set rather than being sensitive to the
+/// order one header includes another in.
+pub fn visible_defs(result: &ScanResult, entry: &Path) -> FileDefs {
+    let mut merged = FileDefs::default();
+
+    for dep in result.transitive_deps(entry) {
+        let Ok(text) = fs::read_to_string(&dep) else {
+            continue;
+        };
+        let Ok((tokens, _)) = lexer_c::tokenize(&text) else {
+            continue;
+        };
+
+        for def_tokens in lexer_c::get_defines(&tokens) {
+            if let Ok(def) = lexer_c::parse_macro_def(def_tokens) {
+                merged
+                    .defines
+                    .insert(def.name.to_string(), Token::tokens_to_string(def_tokens));
+            }
+        }
+        for udt_tokens in lexer_c::get_udts(&tokens) {
+            if let Ok(name) = lexer_c::get_udt_name(udt_tokens) {
+                merged
+                    .udts
+                    .insert(name.to_string(), Token::tokens_to_string(udt_tokens));
+            }
+        }
+    }
+
+    merged
+}
+
+fn is_blank(tok: &Token) -> bool {
+    matches!(tok, Token::Space | Token::Tab)
+}
+
+fn skip_blank(tokens: &[Token], mut i: usize) -> usize {
+    while i < tokens.len() && is_blank(&tokens[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// Collects every `#define` anywhere in `tokens` into a macro table,
+/// regardless of which conditional branch it sits in. This has no macro
+/// expansion of its own, so it can't tell whether a `#define` nested inside
+/// some other `#ifdef` is actually reachable — treating it as defined anyway
+/// is the same better-to-over-include-than-drop-a-real-dependency tradeoff
+/// [`lexer_c::reconstruct_source`] is handed this table for downstream.
+fn collect_defines<'a>(tokens: &'a Vec<Token<'a>>) -> lexer_c::MacroTable<'a> {
+    let mut defines = lexer_c::MacroTable::new();
+    for def_tokens in lexer_c::get_defines(tokens) {
+        if let Ok(def) = lexer_c::parse_macro_def(def_tokens) {
+            defines.insert(def.name, def);
+        }
+    }
+    defines
+}
+
+fn parse_include<'a>(tokens: &[Token<'a>], text: &str, spans: &lexer_c::SourceMap, start: usize) -> Option<(String, bool)> {
+    let i = skip_blank(tokens, start);
+
+    match tokens.get(i)? {
+        Token::Literal(s) => Some((s.trim_matches('"').to_string(), false)),
+        Token::LessThan => {
+            let name_start = i + 1;
+            let mut j = name_start;
+            while j < tokens.len() && tokens[j] != Token::GreaterThan {
+                j += 1;
+            }
+            if j >= tokens.len() || j == name_start {
+                return None;
+            }
+            let start_byte = spans.span(name_start).start_byte;
+            let end_byte = spans.span(j - 1).end_byte;
+            Some((text[start_byte..end_byte].to_string(), true))
+        }
+        _ => None,
+    }
+}
+
+fn resolve(including_file: &Path, name: &str, system: bool, include_dirs: &[PathBuf]) -> Option<PathBuf> {
+    if !system {
+        if let Some(dir) = including_file.parent() {
+            let candidate = dir.join(name);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+
+    for dir in include_dirs {
+        let candidate = dir.join(name);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+    }
+
+    None
+}
+
+/// Scans `text` for `#include` directives, first dropping every branch of a
+/// `#if`/`#ifdef`/`#ifndef` chain that isn't taken (via the same
+/// [`lexer_c::get_conditional_regions`]/[`lexer_c::reconstruct_source`] pass
+/// `header_gen` uses to flatten a header for emission) so an include guarded
+/// by e.g. `#if 0` or an `#ifdef` of a macro nothing in this file `#define`s
+/// can't create a phantom dependency.
+fn scan_file(path: &Path, text: &str, include_dirs: &[PathBuf]) -> Vec<RawInclude> {
+    let Ok((tokens, _)) = lexer_c::tokenize(text) else {
+        return vec![];
+    };
+    let defines = collect_defines(&tokens);
+    let reconstructed = lexer_c::reconstruct_source(&tokens, &[], Some(&defines));
+
+    let Ok((tokens, source_map)) = lexer_c::tokenize(&reconstructed) else {
+        return vec![];
+    };
+
+    let mut includes = vec![];
+    let mut idx = 0;
+    while idx < tokens.len() {
+        if tokens[idx] != Token::HashTag {
+            idx += 1;
+            continue;
+        }
+
+        let kw_idx = skip_blank(&tokens, idx + 1);
+        if let Some(Token::Object("include")) = tokens.get(kw_idx) {
+            if let Some((name, system)) = parse_include(&tokens, &reconstructed, &source_map, kw_idx + 1) {
+                let resolved = resolve(path, &name, system, include_dirs);
+                let raw = if system { format!("<{}>", name) } else { format!("\"{}\"", name) };
+                includes.push(RawInclude { raw, resolved });
+            }
+        }
+
+        idx = kw_idx;
+        while idx < tokens.len() && tokens[idx] != Token::NewLine {
+            idx += 1;
+        }
+    }
+
+    includes
+}
+
+/// Per-project cache of each scanned file's content hash, so a later build can
+/// tell whether anything actually changed since the hashes were last recorded
+/// via [`update_cache`]. Keyed by the file's path rendered to a string (rather
+/// than `PathBuf` itself, which `serde_json` can't use as a map key); stored
+/// under [`DEPGRAPH_CACHE_DIR`], one file per project (named by a hash of the
+/// project's own path, so unrelated projects can't collide).
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DepCache {
+    #[serde(default)]
+    hashes: HashMap<String, String>,
+}
+
+fn cache_path(project_dir: &Path) -> PathBuf {
+    let key = content_address(project_dir.to_string_lossy().as_bytes());
+    (*DEPGRAPH_CACHE_DIR).join(format!("{}.json", key))
+}
+
+fn load_cache(project_dir: &Path) -> DepCache {
+    fs::read_to_string(cache_path(project_dir))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(project_dir: &Path, cache: &DepCache) -> Result<()> {
+    let path = cache_path(project_dir);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(cache)?)?;
+    Ok(())
+}
+
+/// Returns the subset of `entry_files` whose own content, or that of any
+/// header they transitively `#include`, has changed since the last
+/// [`update_cache`] call for this project. `result` should be the return
+/// value of a `scan` call that covered `entry_files`.
+pub fn stale_entries(project_dir: &Path, entry_files: &[PathBuf], result: &ScanResult) -> Vec<PathBuf> {
+    let cache = load_cache(project_dir);
+
+    entry_files
+        .iter()
+        .filter(|entry| {
+            result.transitive_deps(entry).into_iter().any(|dep| {
+                match fs::read(&dep) {
+                    Ok(bytes) => {
+                        cache.hashes.get(&dep.to_string_lossy().to_string()) != Some(&content_address(&bytes))
+                    }
+                    Err(_) => true,
+                }
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Records the current content hash of every file reachable (per `result`'s
+/// graph) from `entry_files`, so the next [`stale_entries`] call can detect
+/// what changed.
+pub fn update_cache(project_dir: &Path, entry_files: &[PathBuf], result: &ScanResult) -> Result<()> {
+    let mut cache = load_cache(project_dir);
+
+    for entry in entry_files {
+        for dep in result.transitive_deps(entry) {
+            if let Ok(bytes) = fs::read(&dep) {
+                cache.hashes.insert(dep.to_string_lossy().to_string(), content_address(&bytes));
+            }
+        }
+    }
+
+    save_cache(project_dir, &cache)
+}
@@ -1,11 +1,88 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use anyhow::{anyhow, Result};
+use unicode_xid::UnicodeXID;
+
+use crate::utils::Language;
+
+/// The byte range a token occupied in the source it was lexed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+/// A 1-indexed line/column position, as resolved by [`SourceMap::locate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Side table pairing `tokenize`'s token stream with where each token came
+/// from in the original source, modeled on proc-macro2's span/source-map
+/// split: tokens themselves stay plain `Token` values (so every existing
+/// `&[Token]` consumer in this module keeps working unchanged), and position
+/// information is looked up by index only when a caller actually needs it
+/// (diagnostics, IDE tooling).
+///
+/// This is the one piece of plumbing `get_fn_def`/`get_udts` still lack for
+/// user-facing "defined at line:col" diagnostics: they return `&[Token]`
+/// slices, and a caller holding the `SourceMap` a `tokenize` call returned
+/// alongside those tokens can resolve either end of a slice's span with
+/// `locate` to get one. Both `line` and `column` here are 1-indexed, matching
+/// how editors and compilers report positions to users.
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    /// Byte offset of the start of each line, built by a single scan for `\n`.
+    line_starts: Vec<usize>,
+    /// `spans[i]` is the byte range of `tokens[i]` from the paired `tokenize` call.
+    spans: Vec<Span>,
+}
+
+impl SourceMap {
+    fn new(code: &str) -> Self {
+        let mut line_starts = vec![0];
+        for (i, b) in code.as_bytes().iter().enumerate() {
+            if *b == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        SourceMap {
+            line_starts,
+            spans: Vec::with_capacity(4096),
+        }
+    }
+
+    fn push(&mut self, span: Span) {
+        self.spans.push(span);
+    }
+
+    /// Resolves a byte offset into the source into a 1-indexed `(line, column)`,
+    /// via a binary search over the line-start index built in `new`.
+    pub fn locate(&self, byte: usize) -> LineColumn {
+        let line = match self.line_starts.binary_search(&byte) {
+            Ok(i) => i,
+            Err(i) => i.saturating_sub(1),
+        };
+        let column = byte - self.line_starts[line];
+        LineColumn {
+            line: line + 1,
+            column: column + 1,
+        }
+    }
+
+    /// The span of the token at `index` in the token stream this map was built for.
+    pub fn span(&self, index: usize) -> Span {
+        self.spans[index]
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Token<'a> {
     Object(&'a str),
     Literal(&'a str),
+    CharLiteral(&'a str),
+    Number(&'a str),
     Comment(&'a str),
     HashTag,
     GreaterThan,
@@ -39,9 +116,69 @@ pub enum Token<'a> {
     NewLine,
     Space,
     Tab,
+
+    // Compound operators assembled from joint runs of the symbols above by
+    // `assemble_operators` — see that function for the full mapping. Kept as
+    // their own variants (rather than e.g. `Token::Object("==")`) so matching
+    // on `Token::Equal` can't accidentally fire on half of a `==`.
+    Arrow,
+    EqEqual,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    ShiftLeft,
+    ShiftRight,
+    ShiftLeftEqual,
+    ShiftRightEqual,
+    AndAnd,
+    OrOr,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    ModEqual,
+    AmpEqual,
+    PipeEqual,
+    CarrotEqual,
+    ColonColon,
+    PlusPlus,
+    MinusMinus,
+    Ellipsis,
 }
 
 impl<'a> Token<'a> {
+    /// The literal spelling of a compound operator assembled by
+    /// `assemble_operators`, or `None` for anything else (single-char symbols
+    /// are instead recovered via `TOKEN_MAPPING` below).
+    fn compound_spelling(self) -> Option<&'static str> {
+        Some(match self {
+            Token::Arrow => "->",
+            Token::EqEqual => "==",
+            Token::NotEqual => "!=",
+            Token::LessEqual => "<=",
+            Token::GreaterEqual => ">=",
+            Token::ShiftLeft => "<<",
+            Token::ShiftRight => ">>",
+            Token::ShiftLeftEqual => "<<=",
+            Token::ShiftRightEqual => ">>=",
+            Token::AndAnd => "&&",
+            Token::OrOr => "||",
+            Token::PlusEqual => "+=",
+            Token::MinusEqual => "-=",
+            Token::StarEqual => "*=",
+            Token::SlashEqual => "/=",
+            Token::ModEqual => "%=",
+            Token::AmpEqual => "&=",
+            Token::PipeEqual => "|=",
+            Token::CarrotEqual => "^=",
+            Token::ColonColon => "::",
+            Token::PlusPlus => "++",
+            Token::MinusMinus => "--",
+            Token::Ellipsis => "...",
+            _ => return None,
+        })
+    }
+
     pub fn tokens_to_string(tokens: &[Token]) -> String {
         let mut string = String::new();
 
@@ -52,9 +189,18 @@ impl<'a> Token<'a> {
             else if let Token::Literal(s) = t {
                 string.push_str(s);
             }
+            else if let Token::CharLiteral(s) = t {
+                string.push_str(s);
+            }
+            else if let Token::Number(s) = t {
+                string.push_str(s);
+            }
             else if let Token::Comment(c) = t {
                 string.push_str(c);
-            } 
+            }
+            else if let Some(s) = t.compound_spelling() {
+                string.push_str(s);
+            }
             else {
                 for i in 0..TOKEN_MAPPING.len() {
                     if let Some(c) = TOKEN_MAPPING[i] {
@@ -67,64 +213,555 @@ impl<'a> Token<'a> {
         }
         string
     }
+
+    /// The variant name this token serializes as under [`Token::to_wire`] —
+    /// derived from the token's own `Debug` output rather than a hand-kept
+    /// match arm per variant, so a new `Token` variant can't silently fall
+    /// through unlabeled. A payload variant's `Debug` looks like
+    /// `Object("foo")`; stripping from the first `(` onward leaves just the
+    /// variant name.
+    fn wire_kind(&self) -> String {
+        let debug = format!("{:?}", self);
+        match debug.find('(') {
+            Some(i) => debug[..i].to_string(),
+            None => debug,
+        }
+    }
+
+    /// Serializes this token as one `KIND␟SPELLING␟BYTE_START␟BYTE_END`
+    /// line — stable across runs, and unambiguous about whitespace/comment
+    /// tokens the way a bare `.log` dump of `{:?}` isn't. `span` should be
+    /// the [`SourceMap`] entry this token was lexed with (see
+    /// [`tokens_to_wire`]). `SPELLING` is escaped via [`escape_wire`] since a
+    /// `NewLine` token's spelling, or a block comment's, can itself contain a
+    /// `\n` — otherwise one token's dump could split across two lines.
+    /// Assumes the source text doesn't itself contain the ASCII
+    /// unit-separator byte used to delimit fields, which holds for any real
+    /// C/C++ source.
+    pub fn to_wire(&self, span: Span) -> String {
+        format!(
+            "{}{WIRE_SEP}{}{WIRE_SEP}{}{WIRE_SEP}{}",
+            self.wire_kind(),
+            escape_wire(&Token::tokens_to_string(std::slice::from_ref(self))),
+            span.start_byte,
+            span.end_byte,
+        )
+    }
+}
+
+/// Field delimiter for [`Token::to_wire`]/[`from_wire`] — the ASCII unit
+/// separator, chosen so it can't collide with anything that legitimately
+/// appears in C/C++ source or a token's own spelling.
+const WIRE_SEP: char = '\u{1f}';
+
+/// Escapes the two characters [`Token::to_wire`]'s line-oriented format can't
+/// tolerate literally: a backslash (so the escape itself is unambiguous) and
+/// a newline (so a multi-line block comment or a `NewLine` token's own `"\n"`
+/// spelling can't be mistaken for a line break between two tokens).
+fn escape_wire(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// One line of [`Token::to_wire`] output, parsed back by [`from_wire`].
+/// Deliberately doesn't reconstruct a real `Token<'a>` — `kind`/`spelling`
+/// just borrow from the wire text itself, and `spelling` stays
+/// [`escape_wire`]-escaped rather than being unescaped back to the original
+/// text — since the point of the wire format is comparing two independently
+/// produced token dumps (golden-file diffing, round-trip fuzzing) byte for
+/// byte, not feeding tokens back into the rest of this module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireToken<'a> {
+    pub kind: &'a str,
+    pub spelling: &'a str,
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+/// Serializes every token in `tokens` (with spans from `source_map`, which
+/// must be the `SourceMap` paired with `tokens` by the same `tokenize`
+/// call) as [`Token::to_wire`] lines, one per line.
+pub fn tokens_to_wire(tokens: &[Token], source_map: &SourceMap) -> String {
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(i, t)| t.to_wire(source_map.span(i)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses [`tokens_to_wire`]'s output back into [`WireToken`]s, one per
+/// non-empty line. A malformed line (wrong field count, non-numeric byte
+/// offset) is silently skipped rather than erroring — this is a test/fuzz
+/// tool, not a parser on an untrusted input path.
+pub fn from_wire(wire: &str) -> Vec<WireToken<'_>> {
+    wire.lines()
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let mut parts = line.split(WIRE_SEP);
+            let kind = parts.next()?;
+            let spelling = parts.next()?;
+            let start_byte = parts.next()?.parse().ok()?;
+            let end_byte = parts.next()?.parse().ok()?;
+            if parts.next().is_some() {
+                return None;
+            }
+            Some(WireToken { kind, spelling, start_byte, end_byte })
+        })
+        .collect()
+}
+
+/// Compares `expected` (a committed golden file's contents) against
+/// `actual` (freshly generated [`tokens_to_wire`] output) line by line,
+/// returning `None` if they match and otherwise a message naming the first
+/// divergent line with a couple of lines of surrounding context — so a
+/// differential-harness failure points straight at the mismatch instead of
+/// requiring a manual diff of two multi-thousand-line dumps.
+pub fn diff_wire(expected: &str, actual: &str) -> Option<String> {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+
+    let first_mismatch = expected_lines
+        .iter()
+        .zip(actual_lines.iter())
+        .position(|(e, a)| e != a)
+        .unwrap_or_else(|| expected_lines.len().min(actual_lines.len()));
+
+    if first_mismatch == expected_lines.len() && first_mismatch == actual_lines.len() {
+        return None;
+    }
+
+    const CONTEXT: usize = 2;
+    let ctx_start = first_mismatch.saturating_sub(CONTEXT);
+
+    let mut out = format!("token streams diverge at line {}\n", first_mismatch + 1);
+    for i in ctx_start..(first_mismatch + CONTEXT + 1).min(expected_lines.len().max(actual_lines.len())) {
+        let e = expected_lines.get(i).copied().unwrap_or("<missing>");
+        let a = actual_lines.get(i).copied().unwrap_or("<missing>");
+        if i == first_mismatch {
+            out.push_str(&format!("  {:>4} - expected: {}\n", i + 1, e));
+            out.push_str(&format!("       + actual:   {}\n", a));
+        } else {
+            out.push_str(&format!("  {:>4}   {}\n", i + 1, e));
+        }
+    }
+
+    Some(out)
+}
+
+/// Which pair of delimiters bounds a [`Group`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Paren,
+    Bracket,
+    Brace,
+}
+
+/// A balanced delimiter region of the token stream, with everything between
+/// the opening and closing delimiter already grouped one level deeper —
+/// proc-macro2's `Group`. `open_idx`/`close_idx` are the delimiter tokens'
+/// indices in the flat `&[Token]` `build_token_tree` was called with, so code
+/// that still deals in flat slices (the rest of this module) can recover one.
+#[derive(Debug, Clone)]
+pub struct Group<'a> {
+    pub delimiter: Delimiter,
+    pub contents: Vec<TokenTree<'a>>,
+    pub open_idx: usize,
+    pub close_idx: usize,
+}
+
+/// One node of the tree `build_token_tree` assembles: either a single token
+/// (tagged with its flat index) or a balanced [`Group`].
+#[derive(Debug, Clone)]
+pub enum TokenTree<'a> {
+    Leaf(Token<'a>, usize),
+    Group(Group<'a>),
+}
+
+impl<'a> TokenTree<'a> {
+    /// The flat index of the first token this node covers.
+    fn start_idx(&self) -> usize {
+        match self {
+            TokenTree::Leaf(_, i) => *i,
+            TokenTree::Group(g) => g.open_idx,
+        }
+    }
+}
+
+/// What kind of structural problem a [`LexError`] is reporting, so callers
+/// can match on the failure mode (e.g. to choose a recovery strategy)
+/// instead of parsing `message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexErrorKind {
+    /// A `"..."` string literal or `'...'` char literal ran off the end of
+    /// its line (or the file) with no closing quote.
+    UnterminatedString,
+    /// A `/* ...` block comment ran off the end of the file with no `*/`.
+    UnterminatedComment,
+    /// A paren/bracket/brace was never closed, or a closing delimiter
+    /// didn't match the one it was supposed to close — raised by `build_token_tree`.
+    UnmatchedDelimiter,
+    /// A `#define`/UDT extractor couldn't find the name it expects in the
+    /// shape it expects (e.g. two `#define`s in one token run, a `struct`
+    /// with no name, a define with no identifier after it).
+    MalformedMacro,
+}
+
+/// A recoverable lexing failure, carrying the [`LexErrorKind`] and — when
+/// the failing code had a `Span` to attach — the byte range where it was
+/// detected, so callers can render source context via [`render_lex_error`].
+/// `span` is `None` for failures raised against an isolated token slice with
+/// no accompanying `SourceMap` (e.g. `get_udt_name` on a slice already
+/// carved out by `get_udts`).
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} (bytes {}..{})", self.message, span.start_byte, span.end_byte),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Renders a `LexError` against the `source` it was raised from in the
+/// codespan-reporting style C tooling uses: the line number, the offending
+/// source line, and a caret/underline under the span. Falls back to the bare
+/// message when `err.span` is `None` (a failure with no known position —
+/// see [`get_udt_name`]/[`get_define_name`]).
+pub fn render_lex_error(source: &str, source_map: &SourceMap, err: &LexError) -> String {
+    let Some(span) = err.span else {
+        return format!("error: {}", err.message);
+    };
+
+    let loc = source_map.locate(span.start_byte);
+    let line_start = span.start_byte - (loc.column - 1);
+    let line_end = source[line_start..]
+        .find('\n')
+        .map(|i| line_start + i)
+        .unwrap_or(source.len());
+    let line_text = &source[line_start..line_end];
+
+    let underline_len = span.end_byte.saturating_sub(span.start_byte).max(1);
+    let underline = format!("{}{}", " ".repeat(loc.column - 1), "^".repeat(underline_len));
+
+    format!(
+        "error: {}\n  --> line {}:{}\n   |\n{:>3} | {}\n   | {}",
+        err.message, loc.line, loc.column, loc.line, line_text, underline
+    )
+}
+
+fn span_at(spans: Option<&[Span]>, idx: usize) -> Option<Span> {
+    spans.and_then(|s| s.get(idx).copied())
+}
+
+/// Groups matched `()`/`[]`/`{}` regions of `tokens` into nested [`Group`]
+/// nodes in a single pass, replacing the ad-hoc brace-counters that used to
+/// be hand-rolled separately in `get_udts`, `get_fn_def`, and `get_udt_name`.
+/// `spans` (as returned alongside `tokens` by `tokenize`) is used only to
+/// attach a byte range to a `LexError`; pass `None` if it isn't available
+/// (callers that already hold an isolated token slice with no source map).
+pub fn build_token_tree<'a>(
+    tokens: &'a [Token<'a>],
+    spans: Option<&[Span]>,
+) -> std::result::Result<Vec<TokenTree<'a>>, LexError> {
+    fn build<'a>(
+        tokens: &'a [Token<'a>],
+        spans: Option<&[Span]>,
+        idx: &mut usize,
+        expected_close: Option<Token<'a>>,
+    ) -> std::result::Result<Vec<TokenTree<'a>>, LexError> {
+        let mut out = vec![];
+
+        while *idx < tokens.len() {
+            let tok = tokens[*idx];
+
+            if matches!(tok, Token::CloseParen | Token::CloseSquareBracket | Token::CloseCurlyBrace) {
+                if Some(tok) == expected_close {
+                    return Ok(out);
+                }
+                return Err(LexError {
+                    kind: LexErrorKind::UnmatchedDelimiter,
+                    message: "Unmatched or mismatched closing delimiter".to_string(),
+                    span: span_at(spans, *idx),
+                });
+            }
+
+            let opening = match tok {
+                Token::OpenParen => Some((Delimiter::Paren, Token::CloseParen)),
+                Token::OpenSquareBracket => Some((Delimiter::Bracket, Token::CloseSquareBracket)),
+                Token::OpenCurlyBrace => Some((Delimiter::Brace, Token::CloseCurlyBrace)),
+                _ => None,
+            };
+
+            let Some((delimiter, close_tok)) = opening else {
+                out.push(TokenTree::Leaf(tok, *idx));
+                *idx += 1;
+                continue;
+            };
+
+            let open_idx = *idx;
+            *idx += 1;
+            let contents = build(tokens, spans, idx, Some(close_tok))?;
+
+            if *idx >= tokens.len() {
+                return Err(LexError {
+                    kind: LexErrorKind::UnmatchedDelimiter,
+                    message: format!("Unmatched opening {:?} delimiter", delimiter),
+                    span: span_at(spans, open_idx),
+                });
+            }
+
+            out.push(TokenTree::Group(Group {
+                delimiter,
+                contents,
+                open_idx,
+                close_idx: *idx,
+            }));
+            *idx += 1;
+        }
+
+        if expected_close.is_some() {
+            return Err(LexError {
+                kind: LexErrorKind::UnmatchedDelimiter,
+                message: "Unmatched opening delimiter (reached end of input)".to_string(),
+                span: span_at(spans, tokens.len().saturating_sub(1)),
+            });
+        }
+
+        Ok(out)
+    }
+
+    let mut idx = 0;
+    build(tokens, spans, &mut idx, None)
+}
+
+/// Lexes `code` into a flat token stream, alongside a [`SourceMap`] recording
+/// where each `tokens[i]` came from (`source_map.span(i)`/`source_map.locate(..)`).
+/// Every other function in this module keeps consuming plain `&[Token]` and is
+/// unaware of spans; only callers that need positions (diagnostics, tooling)
+/// need to thread the `SourceMap` through. Returns a positioned [`LexError`]
+/// (renderable via [`render_lex_error`]) instead of panicking on an
+/// unterminated string or block comment.
+pub fn tokenize(code: &str) -> std::result::Result<(Vec<Token>, SourceMap), LexError> {
+    tokenize_lang(code, Language::C)
 }
 
-pub fn tokenize(code: &str) -> Result<Vec<Token>> {
+/// Same as [`tokenize`], but in [`Language::Cpp`] mode an `R` immediately
+/// followed by `"` (no intervening whitespace — `R"delim(...)`) is scanned
+/// as a raw string literal instead of an ordinary object, per
+/// [`find_len_raw_string_literal`]. Pure-C callers keep using [`tokenize`]
+/// so this gate never changes their output.
+pub fn tokenize_lang(code: &str, lang: Language) -> std::result::Result<(Vec<Token>, SourceMap), LexError> {
     let code_bytes = code.as_bytes();
     let mut tokens = Vec::with_capacity(4096);
+    let mut source_map = SourceMap::new(code);
 
     let mut idx: usize = 0;
     while idx < code.len() {
+        let start = idx;
         match code_bytes[idx] as char {
             ' ' => {
                 tokens.push(Token::Space);
                 idx += 1;
+                source_map.push(Span { start_byte: start, end_byte: idx });
                 continue;
             }
             '\t' => {
                 tokens.push(Token::Tab);
                 idx += 1;
+                source_map.push(Span { start_byte: start, end_byte: idx });
                 continue;
             }
             '\n' => {
                 tokens.push(Token::NewLine);
                 idx += 1;
+                source_map.push(Span { start_byte: start, end_byte: idx });
                 continue;
             }
+            'R' if lang == Language::Cpp && code_bytes.get(idx + 1) == Some(&b'"') => {
+                if let Some(len) = find_len_raw_string_literal(&code_bytes[(idx + 1)..]) {
+                    let val = &code[idx..(idx + 1 + len)];
+                    tokens.push(Token::Literal(val));
+                    idx += 1 + len;
+                    source_map.push(Span { start_byte: start, end_byte: idx });
+                    continue;
+                }
+                // `R` not actually followed by a well-formed raw-string
+                // delimiter (e.g. just an identifier starting with `R`) —
+                // fall through to ordinary object scanning below.
+            }
             '"' => {
-                let len = find_len_string_literal(&code_bytes[idx..])?;
+                let len = find_len_string_literal(&code_bytes[idx..], start)?;
                 let val = &code[idx..(idx + len)];
                 let tok = Token::Literal(val);
                 tokens.push(tok);
                 idx += len;
+                source_map.push(Span { start_byte: start, end_byte: idx });
+                continue;
+            }
+            '\'' => {
+                let len = find_len_char_literal(&code_bytes[idx..], start)?;
+                let val = &code[idx..(idx + len)];
+                let tok = Token::CharLiteral(val);
+                tokens.push(tok);
+                idx += len;
+                source_map.push(Span { start_byte: start, end_byte: idx });
                 continue;
             }
             '/' => {
                 if matches!(code_bytes[idx+1] as char, '*' | '/') {
-                    let len = find_len_comment(&code_bytes[idx..]);
+                    let len = find_len_comment(&code_bytes[idx..], start)?;
                     let val = &code[idx..(idx + len)];
                     let tok = Token::Comment(val);
                     tokens.push(tok);
                     idx += len;
+                    source_map.push(Span { start_byte: start, end_byte: idx });
                     continue;
                 }
             }
+            '0'..='9' => {
+                let new_idx = find_len_number(code_bytes, idx);
+                let val = &code[idx..new_idx];
+                tokens.push(Token::Number(val));
+                idx = new_idx;
+                source_map.push(Span { start_byte: start, end_byte: idx });
+                continue;
+            }
+            '.' if code_bytes.get(idx + 1).is_some_and(u8::is_ascii_digit) => {
+                let new_idx = find_len_number(code_bytes, idx);
+                let val = &code[idx..new_idx];
+                tokens.push(Token::Number(val));
+                idx = new_idx;
+                source_map.push(Span { start_byte: start, end_byte: idx });
+                continue;
+            }
             _ => {}
         }
 
         if let Some(sym) = is_symbol(&code[idx..]) {
             tokens.push(sym);
             idx += 1;
+            source_map.push(Span { start_byte: start, end_byte: idx });
             continue;
         }
-        let new_idx = find_len_object(code_bytes, idx);
+
+        // A non-ASCII char that isn't a valid identifier start per
+        // `UnicodeXID` (an emoji, currency sign, etc. — anything `is_symbol`
+        // has no ASCII mapping for) shouldn't greedily swallow whatever
+        // identifier-like text follows it. Emit it as its own
+        // single-codepoint `Object` instead of handing it to
+        // `find_len_object`, which only knows how to *continue* an object
+        // once one has legitimately started.
+        let lead = code[idx..].chars().next().unwrap();
+        if !lead.is_ascii() && !lead.is_xid_start() {
+            let new_idx = idx + lead.len_utf8();
+            tokens.push(Token::Object(&code[idx..new_idx]));
+            idx = new_idx;
+            source_map.push(Span { start_byte: start, end_byte: idx });
+            continue;
+        }
+
+        let new_idx = find_len_object(code, idx);
         let val = &code[idx..new_idx];
         let tok = Token::Object(val);
         tokens.push(tok);
         idx = new_idx;
+        source_map.push(Span { start_byte: start, end_byte: idx });
     }
 
-    Ok(tokens)
+    let raw_spans = std::mem::take(&mut source_map.spans);
+    let (tokens, spans) = assemble_operators(tokens, raw_spans);
+    source_map.spans = spans;
+
+    Ok((tokens, source_map))
+}
+
+/// Whether two adjacent tokens' spans directly abut, i.e. there was no
+/// whitespace/comment between them in the source — proc-macro2's
+/// `Spacing::Joint` versus `Spacing::Alone` on a `Punct`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Spacing {
+    Joint,
+    Alone,
+}
+
+fn spacing(a: Span, b: Span) -> Spacing {
+    if a.end_byte == b.start_byte {
+        Spacing::Joint
+    } else {
+        Spacing::Alone
+    }
+}
+
+/// Coalesces maximal joint (no intervening whitespace) runs of single-char
+/// punctuation tokens that spell one of C's compound operators into a single
+/// token, longest match first (so `<<=` wins over `<<` followed by `=`).
+/// Without this, `->`, `==`, `<<`, `&&`, `::`, `+=`, `/=`, etc. are
+/// indistinguishable from two unrelated symbols that happen to sit next to
+/// each other, and `get_fn_def`/`reconstruct_source` can't round-trip them
+/// faithfully.
+fn assemble_operators<'a>(tokens: Vec<Token<'a>>, spans: Vec<Span>) -> (Vec<Token<'a>>, Vec<Span>) {
+    let compound_ops: &[(&[Token<'a>], Token<'a>)] = &[
+        (&[Token::LessThan, Token::LessThan, Token::Equal], Token::ShiftLeftEqual),
+        (&[Token::GreaterThan, Token::GreaterThan, Token::Equal], Token::ShiftRightEqual),
+        (&[Token::Period, Token::Period, Token::Period], Token::Ellipsis),
+        (&[Token::Minus, Token::GreaterThan], Token::Arrow),
+        (&[Token::Equal, Token::Equal], Token::EqEqual),
+        (&[Token::Exclamation, Token::Equal], Token::NotEqual),
+        (&[Token::LessThan, Token::Equal], Token::LessEqual),
+        (&[Token::GreaterThan, Token::Equal], Token::GreaterEqual),
+        (&[Token::LessThan, Token::LessThan], Token::ShiftLeft),
+        (&[Token::GreaterThan, Token::GreaterThan], Token::ShiftRight),
+        (&[Token::Ampersand, Token::Ampersand], Token::AndAnd),
+        (&[Token::Pipe, Token::Pipe], Token::OrOr),
+        (&[Token::Plus, Token::Equal], Token::PlusEqual),
+        (&[Token::Minus, Token::Equal], Token::MinusEqual),
+        (&[Token::Asterisk, Token::Equal], Token::StarEqual),
+        (&[Token::ForwardSlash, Token::Equal], Token::SlashEqual),
+        (&[Token::ModOperator, Token::Equal], Token::ModEqual),
+        (&[Token::Ampersand, Token::Equal], Token::AmpEqual),
+        (&[Token::Pipe, Token::Equal], Token::PipeEqual),
+        (&[Token::Carrot, Token::Equal], Token::CarrotEqual),
+        (&[Token::Colon, Token::Colon], Token::ColonColon),
+        (&[Token::Plus, Token::Plus], Token::PlusPlus),
+        (&[Token::Minus, Token::Minus], Token::MinusMinus),
+    ];
+
+    let mut new_tokens = Vec::with_capacity(tokens.len());
+    let mut new_spans = Vec::with_capacity(spans.len());
+
+    let mut i = 0;
+    'outer: while i < tokens.len() {
+        for &(pattern, merged) in compound_ops {
+            let end = i + pattern.len();
+            if end > tokens.len() || tokens[i..end] != *pattern {
+                continue;
+            }
+            let all_joint = (i..end - 1).all(|k| spacing(spans[k], spans[k + 1]) == Spacing::Joint);
+            if !all_joint {
+                continue;
+            }
+            new_tokens.push(merged);
+            new_spans.push(Span {
+                start_byte: spans[i].start_byte,
+                end_byte: spans[end - 1].end_byte,
+            });
+            i = end;
+            continue 'outer;
+        }
+        new_tokens.push(tokens[i]);
+        new_spans.push(spans[i]);
+        i += 1;
+    }
+
+    (new_tokens, new_spans)
 }
 
 #[inline]
@@ -132,7 +769,7 @@ fn is_symbol(code: &str) -> Option<Token> {
     let char = code.chars().next();
     if let Some(char) = char {
         let char_code = char as usize;
-        if char_code > TOKEN_MAPPING.len() {
+        if char_code >= TOKEN_MAPPING.len() {
             return None;
         }
         return TOKEN_MAPPING[char_code];
@@ -140,50 +777,199 @@ fn is_symbol(code: &str) -> Option<Token> {
     None
 }
 
-fn find_len_object(code_bytes: &[u8], mut curr_idx: usize) -> usize {
-    curr_idx += 1;
-    while curr_idx < code_bytes.len() {
-        let ascii_char = code_bytes[curr_idx] as usize;
-        if ascii_char < TOKEN_MAPPING.len() {
-            if TOKEN_MAPPING[ascii_char].is_some() || ascii_char == ' ' as usize {
-                return curr_idx;
+/// Finds the end of the `Object` token starting at `code[curr_idx]`, walking
+/// whole `char`s (not bytes) so a multibyte identifier — legal in C23 and
+/// common in non-ASCII comments/string bodies lexed as objects — stays
+/// intact instead of being split mid-sequence and producing a byte offset
+/// `&code[..]` would panic slicing on. The ASCII fast path (a plain
+/// `TOKEN_MAPPING` lookup) still handles the common case; a non-ASCII `char`
+/// only continues the object while `is_xid_continue` says it can join an
+/// identifier, so e.g. an em dash still ends it. `is_xid_start` isn't
+/// consulted here: the first `char` of the object was already accepted by
+/// whatever called `find_len_object` (it fell through `is_symbol`, and — for
+/// a non-ASCII lead char — `tokenize`'s own `is_xid_start` check), so
+/// there's nothing left to validate at that position.
+fn find_len_object(code: &str, curr_idx: usize) -> usize {
+    let mut chars = code[curr_idx..].char_indices();
+    chars.next(); // the first char was already confirmed not a symbol by `is_symbol`
+
+    for (offset, c) in chars {
+        let char_code = c as usize;
+        if char_code < TOKEN_MAPPING.len() {
+            if TOKEN_MAPPING[char_code].is_some() || c == ' ' {
+                return curr_idx + offset;
+            }
+        } else if !c.is_xid_continue() {
+            return curr_idx + offset;
+        }
+    }
+
+    code.len()
+}
+
+/// Finds the end of the numeric literal starting at `code_bytes[curr_idx]`
+/// (a digit, or a `.` directly followed by one). Consumes an optional
+/// `0x`/`0X`/`0b`/`0B` radix prefix, digits (with `'` digit separators, as
+/// C++14 allows), an optional `.` fractional part, an optional exponent
+/// (`e`/`E` for decimal, `p`/`P` for a hex float, either with an optional
+/// sign) and a trailing integer/float suffix (`u`/`U`/`l`/`L`/`f`/`F` in any
+/// combination) — the same shape `1.5e-3f`/`0xFFu`/`100'000UL` takes in real
+/// C/C++ source. Doesn't validate the suffix is a *legal* combination (e.g.
+/// `123.4L` vs `123ff`); like `find_len_object`, it only finds where the
+/// token ends.
+fn find_len_number(code_bytes: &[u8], curr_idx: usize) -> usize {
+    let mut idx = curr_idx;
+    let is_hex_radix = code_bytes[idx] == b'0'
+        && matches!(code_bytes.get(idx + 1), Some(b'x' | b'X'));
+    if is_hex_radix || (code_bytes[idx] == b'0' && matches!(code_bytes.get(idx + 1), Some(b'b' | b'B'))) {
+        idx += 2;
+    }
+
+    let is_digit_part = |b: u8| -> bool {
+        b.is_ascii_digit() || b == b'\'' || (is_hex_radix && b.is_ascii_hexdigit())
+    };
+    while idx < code_bytes.len() && is_digit_part(code_bytes[idx]) {
+        idx += 1;
+    }
+
+    if code_bytes.get(idx) == Some(&b'.') {
+        idx += 1;
+        while idx < code_bytes.len() && is_digit_part(code_bytes[idx]) {
+            idx += 1;
+        }
+    }
+
+    let exp_markers: &[u8] = if is_hex_radix { b"pP" } else { b"eE" };
+    if let Some(&marker) = code_bytes.get(idx) {
+        if exp_markers.contains(&marker) {
+            let mut look = idx + 1;
+            if matches!(code_bytes.get(look), Some(b'+' | b'-')) {
+                look += 1;
+            }
+            if matches!(code_bytes.get(look), Some(b) if b.is_ascii_digit()) {
+                idx = look;
+                while idx < code_bytes.len() && code_bytes[idx].is_ascii_digit() {
+                    idx += 1;
+                }
             }
         }
-        curr_idx += 1;
     }
-    return curr_idx;
+
+    while matches!(code_bytes.get(idx), Some(b'u' | b'U' | b'l' | b'L' | b'f' | b'F')) {
+        idx += 1;
+    }
+
+    idx
 }
 
-/// `code_bytes` must be a slice such that the start of the slice is the same as the start of the string (first character must be a `"`)
-fn find_len_string_literal(code_bytes: &[u8]) -> Result<usize> {
+/// `code_bytes` must be a slice such that the start of the slice is the same as the start of the string (first character must be a `"`).
+/// `base_offset` is that slice's absolute byte position in the original source, so a
+/// reported [`LexError`] carries a real [`Span`] rather than one relative to the slice.
+fn find_len_string_literal(code_bytes: &[u8], base_offset: usize) -> std::result::Result<usize, LexError> {
     let mut idx: usize = 1;
     while idx < code_bytes.len() {
         if code_bytes[idx] == '\n' as u8 {
             break;
         }
+        if code_bytes[idx] == '\\' as u8 {
+            idx += 2;
+            continue;
+        }
         if code_bytes[idx] == '"' as u8 {
-            if code_bytes[idx] != '\\' as u8 {
-                idx += 1;
-                return Ok(idx);
-            }
+            idx += 1;
+            return Ok(idx);
+        }
+        idx += 1;
+    }
+    Err(LexError {
+        kind: LexErrorKind::UnterminatedString,
+        message: "String literal not closed".to_string(),
+        span: Some(Span { start_byte: base_offset, end_byte: base_offset + idx }),
+    })
+}
+
+/// Length of a C++11 raw string literal `R"delim(...)delim"`, `code_bytes`
+/// starting at the opening `"` (the `R` prefix sits just before this slice;
+/// `tokenize_lang` folds it into the resulting `Literal` token). Returns
+/// `None` if this isn't actually a raw string — a `)`, backslash or
+/// whitespace byte before the delimiter's `(`, no `(` at all, or no matching
+/// `)delim"` before EOF — so the caller falls back to ordinary object
+/// scanning starting at the `R`.
+fn find_len_raw_string_literal(code_bytes: &[u8]) -> Option<usize> {
+    let mut idx = 1;
+    let delim_start = idx;
+    while idx < code_bytes.len() && code_bytes[idx] != b'(' {
+        if matches!(code_bytes[idx], b')' | b'\\') || code_bytes[idx].is_ascii_whitespace() {
+            return None;
+        }
+        idx += 1;
+    }
+    if idx >= code_bytes.len() {
+        return None;
+    }
+    let delim = &code_bytes[delim_start..idx];
+    idx += 1; // past the '('
+
+    let closer_len = delim.len() + 2; // ")" + delim + "\""
+    while idx + closer_len <= code_bytes.len() {
+        if code_bytes[idx] == b')'
+            && &code_bytes[(idx + 1)..(idx + 1 + delim.len())] == delim
+            && code_bytes[idx + 1 + delim.len()] == b'"'
+        {
+            return Some(idx + closer_len);
+        }
+        idx += 1;
+    }
+    None
+}
+
+/// `code_bytes` must be a slice such that the start of the slice is the same as the start of the
+/// char literal (first character must be a `'`). `base_offset` is used the same way as in
+/// [`find_len_string_literal`].
+fn find_len_char_literal(code_bytes: &[u8], base_offset: usize) -> std::result::Result<usize, LexError> {
+    let mut idx: usize = 1;
+    while idx < code_bytes.len() {
+        if code_bytes[idx] == '\n' as u8 {
+            break;
+        }
+        if code_bytes[idx] == '\\' as u8 {
+            idx += 2;
+            continue;
+        }
+        if code_bytes[idx] == '\'' as u8 {
+            idx += 1;
+            return Ok(idx);
         }
         idx += 1;
     }
-    Err(anyhow!("String literal not closed"))
+    Err(LexError {
+        kind: LexErrorKind::UnterminatedString,
+        message: "Character literal not closed".to_string(),
+        span: Some(Span { start_byte: base_offset, end_byte: base_offset + idx }),
+    })
 }
 
-/// `code_bytes` must be a slice such that the start of the slice is the same as the start of the comment (first characters must be `//` or `/*`)
-fn find_len_comment(code_bytes: &[u8]) -> usize {
+/// `code_bytes` must be a slice such that the start of the slice is the same as the start of the comment (first characters must be `//` or `/*`).
+/// `base_offset` is that slice's absolute byte position in the original source, used the
+/// same way as in [`find_len_string_literal`].
+fn find_len_comment(code_bytes: &[u8], base_offset: usize) -> std::result::Result<usize, LexError> {
     #[cfg(debug_assertions)] {
         if code_bytes[0] != '/' as u8 || !(matches!(code_bytes[1] as char, '*' | '/')){
             panic!("Not a comment");
-        }    
+        }
     }
 
     let mut idx = 2;
     match code_bytes[1] as char {
         '*' => {
-            while idx < code_bytes.len() {
+            loop {
+                if idx + 1 >= code_bytes.len() {
+                    return Err(LexError {
+                        kind: LexErrorKind::UnterminatedComment,
+                        message: "Block comment not closed".to_string(),
+                        span: Some(Span { start_byte: base_offset, end_byte: base_offset + code_bytes.len() }),
+                    });
+                }
                 if code_bytes[idx] == '*' as u8 && code_bytes[idx+1] == '/' as u8 {
                     idx += 2;
                     break;
@@ -199,16 +985,549 @@ fn find_len_comment(code_bytes: &[u8]) -> usize {
         _ => unsafe { std::hint::unreachable_unchecked() },
     }
 
-    idx
+    Ok(idx)
+}
+
+
+/// Which preprocessor conditional directive opened a branch of an
+/// `#if`/`#ifdef`/`#ifndef` ... `#endif` chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionalKind {
+    If,
+    Ifdef,
+    Ifndef,
+    Elif,
+    Else,
+}
+
+impl ConditionalKind {
+    fn from_directive(name: &str) -> Option<Self> {
+        Some(match name {
+            "if" => ConditionalKind::If,
+            "ifdef" => ConditionalKind::Ifdef,
+            "ifndef" => ConditionalKind::Ifndef,
+            "elif" => ConditionalKind::Elif,
+            "else" => ConditionalKind::Else,
+            _ => return None,
+        })
+    }
+}
+
+/// One branch of a conditional chain: the directive that opened it, its raw
+/// condition tokens (empty for `#else`), the flat token range of the body it
+/// guards (directive line excluded), and any conditional chains nested
+/// directly inside that body.
+#[derive(Debug, Clone)]
+pub struct ConditionalBranch<'a> {
+    pub kind: ConditionalKind,
+    pub condition: &'a [Token<'a>],
+    pub body_start: usize,
+    pub body_end: usize,
+    pub nested: Vec<ConditionalRegion<'a>>,
+}
+
+/// A full `#if`/`#ifdef`/`#ifndef` ... `#endif` chain: every branch (`#if`,
+/// any `#elif`s, an optional `#else`) in source order, plus the flat token
+/// range of the whole chain including its directive lines.
+#[derive(Debug, Clone)]
+pub struct ConditionalRegion<'a> {
+    pub branches: Vec<ConditionalBranch<'a>>,
+    pub start_idx: usize,
+    pub end_idx: usize,
+}
+
+/// The flat index of the first `Object` token at or after `from`, skipping
+/// only `Space`/`Tab` (not `NewLine` — a directive name must be on the same
+/// line as its `#`). `None` if the rest of the line has no directive name.
+fn next_directive_name_idx(tokens: &[Token], from: usize) -> Option<usize> {
+    let mut i = from;
+    while i < tokens.len() {
+        match tokens[i] {
+            Token::Space | Token::Tab => i += 1,
+            Token::Object(_) => return Some(i),
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Parses one `#if`/`#ifdef`/`#ifndef` ... `#endif` chain starting at
+/// `tokens[*idx]` (which must be the `#` of its opening directive), advancing
+/// `*idx` to just past the matching `#endif` (or to `tokens.len()` if the
+/// chain is never closed). Recurses into any nested chain found in a
+/// branch's body, mirroring `build_token_tree`'s single-pass recursive shape.
+fn parse_conditional_chain<'a>(tokens: &'a [Token<'a>], idx: &mut usize) -> ConditionalRegion<'a> {
+    let region_start = *idx;
+    let name_idx = next_directive_name_idx(tokens, *idx + 1)
+        .expect("caller only invokes this at a recognized opening directive");
+    let Token::Object(name) = tokens[name_idx] else {
+        unreachable!("caller only invokes this at a recognized opening directive");
+    };
+    let mut branch_kind = ConditionalKind::from_directive(name)
+        .expect("caller only invokes this at a recognized opening directive");
+    let mut cond_start = name_idx + 1;
+    let mut branches = vec![];
+
+    let region_end = loop {
+        let cond_end = if branch_kind == ConditionalKind::Else {
+            cond_start
+        } else {
+            let mut j = cond_start;
+            while j < tokens.len() && tokens[j] != Token::NewLine {
+                j += 1;
+            }
+            j
+        };
+        let condition = &tokens[cond_start..cond_end];
+        let body_start = cond_end;
+
+        let mut nested = vec![];
+        let mut j = body_start;
+        let sibling = loop {
+            if j >= tokens.len() {
+                break None;
+            }
+            if tokens[j] == Token::HashTag {
+                if let Some(ni) = next_directive_name_idx(tokens, j + 1) {
+                    if let Token::Object(n) = tokens[ni] {
+                        if matches!(n, "if" | "ifdef" | "ifndef") {
+                            let mut k = j;
+                            nested.push(parse_conditional_chain(tokens, &mut k));
+                            j = k;
+                            continue;
+                        }
+                        if matches!(n, "elif" | "else" | "endif") {
+                            break Some((n, ni));
+                        }
+                    }
+                }
+            }
+            j += 1;
+        };
+
+        let body_end = j;
+        branches.push(ConditionalBranch {
+            kind: branch_kind,
+            condition,
+            body_start,
+            body_end,
+            nested,
+        });
+
+        match sibling {
+            Some(("elif", next_name_idx)) => {
+                branch_kind = ConditionalKind::Elif;
+                cond_start = next_name_idx + 1;
+            }
+            Some(("else", next_name_idx)) => {
+                branch_kind = ConditionalKind::Else;
+                cond_start = next_name_idx + 1;
+            }
+            Some((_, next_name_idx)) => break next_name_idx + 1, // "endif"
+            None => break tokens.len(),                          // unterminated; EOF closes it
+        }
+    };
+
+    *idx = region_end;
+    ConditionalRegion {
+        branches,
+        start_idx: region_start,
+        end_idx: region_end,
+    }
+}
+
+/// Walks `tokens` tracking a stack of `#if`/`#ifdef`/`#ifndef` directives and
+/// returns every top-level conditional chain as a nested [`ConditionalRegion`]
+/// tree — each region tagged with its branches' condition tokens and the
+/// token range they guard, including branches nested inside one another.
+pub fn get_conditional_regions<'a>(tokens: &'a [Token<'a>]) -> Vec<ConditionalRegion<'a>> {
+    let mut regions = vec![];
+    let mut idx = 0;
+    while idx < tokens.len() {
+        if tokens[idx] == Token::HashTag {
+            if let Some(ni) = next_directive_name_idx(tokens, idx + 1) {
+                if let Token::Object(n) = tokens[ni] {
+                    if matches!(n, "if" | "ifdef" | "ifndef") {
+                        regions.push(parse_conditional_chain(tokens, &mut idx));
+                        continue;
+                    }
+                }
+            }
+        }
+        idx += 1;
+    }
+    regions
+}
+
+/// Replaces every `defined(NAME)`/`defined NAME` in `tokens` with a bare `1`
+/// or `0` object token, resolved against `defines` directly (no macro
+/// expansion) — this has to run *before* `expand_macros` sees the condition,
+/// since `defined`'s operand must name a macro literally, not whatever that
+/// macro itself expands to.
+fn replace_defined<'a>(tokens: &[Token<'a>], defines: &MacroTable<'a>) -> Vec<Token<'a>> {
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if tokens[i] == Token::Object("defined") {
+            let mut j = i + 1;
+            let paren = matches!(tokens.get(j), Some(Token::OpenParen));
+            if paren {
+                j += 1;
+            }
+            if let Some(Token::Object(name)) = tokens.get(j).copied() {
+                j += 1;
+                if paren && matches!(tokens.get(j), Some(Token::CloseParen)) {
+                    j += 1;
+                }
+                out.push(Token::Object(if defines.contains_key(name) { "1" } else { "0" }));
+                i = j;
+                continue;
+            }
+        }
+        out.push(tokens[i]);
+        i += 1;
+    }
+
+    out
+}
+
+/// Parses a C integer literal's value: `0x`/`0X` hex, `0b`/`0B` binary, a
+/// bare leading `0` as octal, and a `u`/`U`/`l`/`L` (in any combination)
+/// suffix, all per C's own integer-literal grammar. Returns `0` for anything
+/// that isn't a valid integer literal — in practice, a leftover identifier
+/// `expand_macros` couldn't resolve (an undefined object-like macro), which
+/// C itself treats as `0` inside a `#if`/`#elif` expression.
+fn parse_int_literal(lit: &str) -> i64 {
+    let digits_end = lit
+        .find(['u', 'U', 'l', 'L'])
+        .unwrap_or(lit.len());
+    let digits = &lit[..digits_end];
+
+    if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        return i64::from_str_radix(hex, 16).unwrap_or(0);
+    }
+    if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        return i64::from_str_radix(bin, 2).unwrap_or(0);
+    }
+    if digits.len() > 1 && digits.starts_with('0') {
+        return i64::from_str_radix(&digits[1..], 8).unwrap_or(0);
+    }
+
+    digits.parse::<i64>().unwrap_or(0)
+}
+
+/// Evaluates a `#if`/`#elif` condition's token slice as a C integer constant
+/// expression, in 64-bit arithmetic, against `defines`. `defined(NAME)`/
+/// `defined NAME` is resolved and replaced with `1`/`0` first (see
+/// `replace_defined`), the rest of the expression is then fully macro-expanded
+/// (see `expand_macros`), and any identifier still left afterwards — an
+/// undefined object-like macro — evaluates to `0`, matching C's own rule for
+/// a plain identifier here. Supports the full grammar C allows in this
+/// position: unary `! ~ - +`; binary `* / % + - << >> < <= > >= == != & ^ |
+/// && ||`; and the ternary `?:`, all at their usual precedence, with `&&`/
+/// `||` short-circuiting (division/modulo by zero evaluates to `0` rather
+/// than panicking, so an unevaluated-in-C side of a short circuit can't
+/// crash this).
+fn eval_condition(tokens: &[Token], defines: &MacroTable) -> i64 {
+    let stripped: Vec<Token> = tokens
+        .iter()
+        .copied()
+        .filter(|t| !matches!(t, Token::Space | Token::Tab | Token::NewLine | Token::Comment(_)))
+        .collect();
+
+    let resolved = replace_defined(&stripped, defines);
+    let expanded: Vec<Token> = match expand_macros(&resolved, defines) {
+        Ok(v) => v,
+        Err(_) => resolved,
+    }
+    .into_iter()
+    .filter(|t| !matches!(t, Token::Space | Token::Tab | Token::NewLine | Token::Comment(_)))
+    .collect();
+
+    let mut idx = 0;
+    eval_ternary(&expanded, &mut idx, defines)
+}
+
+fn eval_ternary(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let cond = eval_logical_or(toks, idx, defines);
+    if matches!(toks.get(*idx), Some(Token::QuestionMark)) {
+        *idx += 1;
+        let then_val = eval_ternary(toks, idx, defines);
+        if matches!(toks.get(*idx), Some(Token::Colon)) {
+            *idx += 1;
+        }
+        let else_val = eval_ternary(toks, idx, defines);
+        if cond != 0 { then_val } else { else_val }
+    } else {
+        cond
+    }
+}
+
+fn eval_logical_or(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_logical_and(toks, idx, defines);
+    while matches!(toks.get(*idx), Some(Token::OrOr)) {
+        *idx += 1;
+        let rhs = eval_logical_and(toks, idx, defines);
+        result = ((result != 0) || (rhs != 0)) as i64;
+    }
+    result
+}
+
+fn eval_logical_and(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_bitor(toks, idx, defines);
+    while matches!(toks.get(*idx), Some(Token::AndAnd)) {
+        *idx += 1;
+        let rhs = eval_bitor(toks, idx, defines);
+        result = ((result != 0) && (rhs != 0)) as i64;
+    }
+    result
+}
+
+fn eval_bitor(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_bitxor(toks, idx, defines);
+    while matches!(toks.get(*idx), Some(Token::Pipe)) {
+        *idx += 1;
+        result |= eval_bitxor(toks, idx, defines);
+    }
+    result
+}
+
+fn eval_bitxor(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_bitand(toks, idx, defines);
+    while matches!(toks.get(*idx), Some(Token::Carrot)) {
+        *idx += 1;
+        result ^= eval_bitand(toks, idx, defines);
+    }
+    result
+}
+
+fn eval_bitand(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_equality(toks, idx, defines);
+    while matches!(toks.get(*idx), Some(Token::Ampersand)) {
+        *idx += 1;
+        result &= eval_equality(toks, idx, defines);
+    }
+    result
+}
+
+fn eval_equality(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_relational(toks, idx, defines);
+    loop {
+        match toks.get(*idx) {
+            Some(Token::EqEqual) => {
+                *idx += 1;
+                result = (result == eval_relational(toks, idx, defines)) as i64;
+            }
+            Some(Token::NotEqual) => {
+                *idx += 1;
+                result = (result != eval_relational(toks, idx, defines)) as i64;
+            }
+            _ => break,
+        }
+    }
+    result
+}
+
+fn eval_relational(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_shift(toks, idx, defines);
+    loop {
+        match toks.get(*idx) {
+            Some(Token::LessThan) => {
+                *idx += 1;
+                result = (result < eval_shift(toks, idx, defines)) as i64;
+            }
+            Some(Token::LessEqual) => {
+                *idx += 1;
+                result = (result <= eval_shift(toks, idx, defines)) as i64;
+            }
+            Some(Token::GreaterThan) => {
+                *idx += 1;
+                result = (result > eval_shift(toks, idx, defines)) as i64;
+            }
+            Some(Token::GreaterEqual) => {
+                *idx += 1;
+                result = (result >= eval_shift(toks, idx, defines)) as i64;
+            }
+            _ => break,
+        }
+    }
+    result
+}
+
+fn eval_shift(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_additive(toks, idx, defines);
+    loop {
+        match toks.get(*idx) {
+            Some(Token::ShiftLeft) => {
+                *idx += 1;
+                let rhs = eval_additive(toks, idx, defines);
+                result = if (0..64).contains(&rhs) { result << rhs } else { 0 };
+            }
+            Some(Token::ShiftRight) => {
+                *idx += 1;
+                let rhs = eval_additive(toks, idx, defines);
+                result = if (0..64).contains(&rhs) { result >> rhs } else { 0 };
+            }
+            _ => break,
+        }
+    }
+    result
+}
+
+fn eval_additive(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_multiplicative(toks, idx, defines);
+    loop {
+        match toks.get(*idx) {
+            Some(Token::Plus) => {
+                *idx += 1;
+                result = result.wrapping_add(eval_multiplicative(toks, idx, defines));
+            }
+            Some(Token::Minus) => {
+                *idx += 1;
+                result = result.wrapping_sub(eval_multiplicative(toks, idx, defines));
+            }
+            _ => break,
+        }
+    }
+    result
+}
+
+fn eval_multiplicative(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    let mut result = eval_unary(toks, idx, defines);
+    loop {
+        match toks.get(*idx) {
+            Some(Token::Asterisk) => {
+                *idx += 1;
+                result = result.wrapping_mul(eval_unary(toks, idx, defines));
+            }
+            Some(Token::ForwardSlash) => {
+                *idx += 1;
+                let rhs = eval_unary(toks, idx, defines);
+                result = if rhs == 0 { 0 } else { result.wrapping_div(rhs) };
+            }
+            Some(Token::ModOperator) => {
+                *idx += 1;
+                let rhs = eval_unary(toks, idx, defines);
+                result = if rhs == 0 { 0 } else { result.wrapping_rem(rhs) };
+            }
+            _ => break,
+        }
+    }
+    result
+}
+
+fn eval_unary(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    match toks.get(*idx) {
+        Some(Token::Exclamation) => {
+            *idx += 1;
+            (eval_unary(toks, idx, defines) == 0) as i64
+        }
+        Some(Token::Tilda) => {
+            *idx += 1;
+            !eval_unary(toks, idx, defines)
+        }
+        Some(Token::Minus) => {
+            *idx += 1;
+            eval_unary(toks, idx, defines).wrapping_neg()
+        }
+        Some(Token::Plus) => {
+            *idx += 1;
+            eval_unary(toks, idx, defines)
+        }
+        _ => eval_primary(toks, idx, defines),
+    }
+}
+
+/// By this point `defined(...)` is already resolved and the expression is
+/// already fully macro-expanded (see `eval_condition`), so the only things
+/// left to find here are a parenthesized sub-expression, an integer literal,
+/// or a leftover identifier (an undefined object-like macro, which C treats
+/// as `0`).
+fn eval_primary(toks: &[Token], idx: &mut usize, defines: &MacroTable) -> i64 {
+    match toks.get(*idx) {
+        Some(Token::OpenParen) => {
+            *idx += 1;
+            let result = eval_ternary(toks, idx, defines);
+            if matches!(toks.get(*idx), Some(Token::CloseParen)) {
+                *idx += 1;
+            }
+            result
+        }
+        Some(Token::Object(lit)) | Some(Token::Number(lit)) => {
+            let lit = *lit;
+            *idx += 1;
+            parse_int_literal(lit)
+        }
+        _ => {
+            *idx += 1;
+            0
+        }
+    }
+}
+
+/// Whether `branch` is the taken branch of its chain given `defines`.
+fn branch_active(branch: &ConditionalBranch, defines: &MacroTable) -> bool {
+    match branch.kind {
+        ConditionalKind::If | ConditionalKind::Elif => {
+            eval_condition(branch.condition, defines) != 0
+        }
+        ConditionalKind::Ifdef => branch
+            .condition
+            .iter()
+            .any(|t| matches!(t, Token::Object(name) if defines.contains_key(name))),
+        ConditionalKind::Ifndef => !branch
+            .condition
+            .iter()
+            .any(|t| matches!(t, Token::Object(name) if defines.contains_key(name))),
+        ConditionalKind::Else => true,
+    }
 }
 
+/// Recursively collects the flat token ranges `reconstruct_source` should
+/// drop to keep only the taken branch of every conditional chain in
+/// `regions` (the chain's directive lines and every branch but the taken
+/// one), given `defines`. A chain with no taken branch (no `#else` and every
+/// condition false) has its entire span dropped.
+fn collect_conditional_drops(
+    regions: &[ConditionalRegion],
+    defines: &MacroTable,
+    drops: &mut Vec<(usize, usize)>,
+) {
+    for region in regions {
+        match region.branches.iter().find(|b| branch_active(b, defines)) {
+            Some(active) => {
+                if region.start_idx < active.body_start {
+                    drops.push((region.start_idx, active.body_start));
+                }
+                if active.body_end < region.end_idx {
+                    drops.push((active.body_end, region.end_idx));
+                }
+                collect_conditional_drops(&active.nested, defines, drops);
+            }
+            None => drops.push((region.start_idx, region.end_idx)),
+        }
+    }
+}
 
-/// Reconstructs the soruce code excluding the ranges specified 
-pub fn reconstruct_source(tokens: &[Token], exclude_ranges: &[&[Token]]) -> String {
+/// Reconstructs the soruce code excluding the ranges specified. `defines`,
+/// when given, is the macro table conditional chains are evaluated against
+/// (as built by a caller from `get_defines`/`parse_macro_def`): chains found
+/// via `get_conditional_regions` then have every branch but the one that's
+/// actually taken dropped as well — `#if`/`#elif` run through the full
+/// constant-expression evaluator in `eval_condition`, so e.g. `#if VERSION >
+/// 2`, an inactive platform `#ifdef`, or an already-included header guard
+/// doesn't reappear in the reconstructed source. Passing `None` reconstructs
+/// exactly as before, leaving every conditional branch in place.
+pub fn reconstruct_source<'a>(
+    tokens: &'a [Token<'a>],
+    exclude_ranges: &[&[Token<'a>]],
+    defines: Option<&MacroTable<'a>>,
+) -> String {
     let mut new_tokens = vec![];
-    
+
     let mut exlcude_map: HashMap<&[Token], Vec<&[Token]>> = HashMap::new();
-    
+
     for &range in exclude_ranges {
         if range.len() < 3 {
             unreachable!();
@@ -218,10 +1537,24 @@ pub fn reconstruct_source(tokens: &[Token], exclude_ranges: &[&[Token]]) -> Stri
         entry.push(range);
     }
 
+    let conditional_drops = match defines {
+        Some(defines) => {
+            let regions = get_conditional_regions(tokens);
+            let mut drops = vec![];
+            collect_conditional_drops(&regions, defines, &mut drops);
+            drops
+        }
+        None => vec![],
+    };
 
     let mut idx = 0;
-    
+
     while idx < tokens.len() {
+        if conditional_drops.iter().any(|&(s, e)| idx >= s && idx < e) {
+            idx += 1;
+            continue;
+        }
+
         if idx + 3 >= tokens.len() {
             new_tokens.push(tokens[idx]);
             idx += 1;
@@ -249,7 +1582,7 @@ pub fn reconstruct_source(tokens: &[Token], exclude_ranges: &[&[Token]]) -> Stri
 
         new_tokens.push(tokens[idx]);
         idx += 1;
-    
+
     }
 
     Token::tokens_to_string(&new_tokens)
@@ -387,66 +1720,105 @@ const TOKEN_MAPPING: [Option<Token>; 128] = [
     None,
 ];
 
+/// Returns the tree index of the first node matching `pred` at or after
+/// `from`, or `tree.len()` if none is found — the tree-walking analogue of
+/// `skip_to`/`skip_to_oneof` below, for code that's already working with a
+/// `TokenTree` instead of a flat `&[Token]`.
+fn skip_to_tree(tree: &[TokenTree<'_>], from: usize, pred: impl Fn(&TokenTree<'_>) -> bool) -> usize {
+    for k in from..tree.len() {
+        if pred(&tree[k]) {
+            return k;
+        }
+    }
+    tree.len()
+}
+
+/// Skips past the first `delimiter`-kind `Group` at or after `from`,
+/// returning the tree index right after it (or `tree.len()` if there isn't
+/// one) — used to jump over a `for (...)`/`while (...)`/`if (...)`
+/// condition no matter how deeply its parens nest.
+fn skip_past_group(tree: &[TokenTree<'_>], from: usize, delimiter: Delimiter) -> usize {
+    for k in from..tree.len() {
+        if let TokenTree::Group(g) = &tree[k] {
+            if g.delimiter == delimiter {
+                return k + 1;
+            }
+        }
+    }
+    tree.len()
+}
+
 // Extracts the function definitions of all non-static functions
 pub fn get_fn_def<'a>(tokens: &'a Vec<Token>) -> Vec<&'a [Token<'a>]> {
     const RESTRICTED_KWARGS: &[&str] = &["for", "while", "if"];
     let mut fn_defs = vec![];
 
-    let mut conditions: [bool; 3];
+    let Ok(tree) = build_token_tree(tokens, None) else {
+        return fn_defs;
+    };
 
-    let mut idx: usize = 0;
-    while idx < tokens.len() {
-        conditions = [
-            false, // Starts with at least two objects
-            false, // Has open paren
-            false, // Has close paren
-        ];
+    let mut i = 0;
+    while i < tree.len() {
+        let TokenTree::Leaf(Token::Object(obj), start_flat_idx) = &tree[i] else {
+            i += 1;
+            continue;
+        };
+        let obj = *obj;
+        let start_flat_idx = *start_flat_idx;
 
-        if let Token::Object(obj) = tokens[idx] {
-            if RESTRICTED_KWARGS.contains(&obj) {
-                skip_to(tokens, Token::CloseParen, &mut idx);
-                continue;
-            } else if obj == "include" {
-                skip_to_oneof(tokens, &[Token::GreaterThan, Token::Literal("\"")], &mut idx);
-                continue;
-            } else if obj == "define" {
-                skip_to(tokens, Token::NewLine, &mut idx);
-                continue;
-            } else if obj == "static" {
-                skip_to_oneof(tokens, &[Token::OpenParen, Token::OpenCurlyBrace], &mut idx);
-                continue;
-            } else if matches!(obj, "return" | "if") {
-                idx += 1;
-                continue;
-            }
+        if RESTRICTED_KWARGS.contains(&obj) {
+            i = skip_past_group(&tree, i + 1, Delimiter::Paren);
+            continue;
+        } else if obj == "include" {
+            i = skip_to_tree(&tree, i + 1, |t| {
+                matches!(t, TokenTree::Leaf(Token::GreaterThan, _) | TokenTree::Leaf(Token::Literal(_), _))
+            });
+            continue;
+        } else if obj == "define" {
+            i = skip_to_tree(&tree, i + 1, |t| matches!(t, TokenTree::Leaf(Token::NewLine, _)));
+            continue;
+        } else if obj == "static" {
+            i = skip_to_tree(&tree, i + 1, |t| {
+                matches!(t, TokenTree::Group(g) if matches!(g.delimiter, Delimiter::Paren | Delimiter::Brace))
+            });
+            continue;
+        } else if matches!(obj, "return" | "if") {
+            i += 1;
+            continue;
+        }
 
-            let mut j = idx + 1;
-            while j < tokens.len() {
-                if let Token::Object(obj_2) = tokens[j] {
-                    if RESTRICTED_KWARGS.contains(&obj_2) || obj_2 == "main" {
+        // Starts with at least two objects and a matched `(...)` before the body.
+        let mut conditions = [false, false];
+        let mut j = i + 1;
+        let mut body_start: Option<usize> = None;
+        while j < tree.len() {
+            match &tree[j] {
+                TokenTree::Leaf(Token::Object(obj_2), _) => {
+                    if RESTRICTED_KWARGS.contains(obj_2) || *obj_2 == "main" {
                         break;
                     }
                     conditions[0] = true;
-                } else if let Token::OpenParen = tokens[j] {
+                }
+                TokenTree::Group(g) if g.delimiter == Delimiter::Paren => {
                     conditions[1] = true;
-                } else if let Token::CloseParen = tokens[j] {
-                    conditions[2] = true;
-                } else if let Token::OpenCurlyBrace = tokens[j] {
-                    if conditions.iter().all(|&i| i) {
-                        fn_defs.push(&tokens[idx..j]);
+                }
+                TokenTree::Group(g) if g.delimiter == Delimiter::Brace => {
+                    if conditions.iter().all(|&c| c) {
+                        body_start = Some(g.open_idx);
                     }
                     break;
-                } else if let Token::Semicolon = tokens[j] {
-                    break;
-                } else if let Token::Equal = tokens[j] {
-                    break;
                 }
-                j += 1;
+                TokenTree::Leaf(Token::Semicolon, _) => break,
+                TokenTree::Leaf(Token::Equal, _) => break,
+                _ => {}
             }
-            idx = j + 1;
-            continue;
+            j += 1;
         }
-        idx += 1;
+
+        if let Some(body_start) = body_start {
+            fn_defs.push(&tokens[start_flat_idx..body_start]);
+        }
+        i = j + 1;
     }
 
     fn_defs
@@ -478,73 +1850,18 @@ pub fn get_includes<'a>(tokens: &'a Vec<Token>) -> Vec<&'a [Token<'a>]> {
     includes
 }
 
-/// Extracts the user defined types (UDTs)
+/// Extracts the user defined types (UDTs). A UDT is an `Object("struct"
+/// | "enum" | "union")` (optionally preceded by `typedef`) followed
+/// eventually by either a brace `Group` (its body) or a bare `;` (a forward
+/// declaration / plain alias) — matched via the token tree rather than a
+/// hand-rolled brace-depth counter, since nested braces inside the body are
+/// already collapsed into that one `Group` node.
+///
+/// Pure-C convenience wrapper around [`get_udts_with_lang`] in
+/// [`Language::C`] mode; use that directly for C++'s `class`/`namespace`/
+/// `template` UDTs.
 pub fn get_udts<'a>(tokens: &'a Vec<Token>) -> Vec<&'a [Token<'a>]> {
-    let mut udts = vec![];
-    if tokens.len() < 3 {
-        return udts;
-    }
-
-    let mut idx: usize = 0;
-    while idx < tokens.len() - 2 {
-        if let Token::Object(obj) = tokens[idx] {
-            if !matches!(obj, "typedef" | "struct" | "union" | "enum") {
-                idx += 1;
-                continue;
-            } 
-
-            let next_idx = if obj == "typedef" {
-                let x = idx + next_non_whitespace_token(&tokens[idx..]);
-                if x >= tokens.len() {
-                    unreachable!();
-                }
-                x
-            }
-            else {
-                idx
-            };
-
-            match tokens[next_idx] {
-                Token::Object("struct") |
-                Token::Object("enum") |
-                Token::Object("union") => {
-                    let start_idx = idx;
-                    idx = next_idx;
-                    let mut curlybrace_stack = 0;
-
-                    while idx < tokens.len() {
-                        match tokens[idx] {
-                            Token::OpenCurlyBrace => curlybrace_stack += 1,
-                            Token::CloseCurlyBrace => {
-                                if curlybrace_stack == 0 {
-                                    unreachable!();
-                                }
-
-                                curlybrace_stack -= 1;
-                            }
-                            Token::Semicolon => {
-                                if curlybrace_stack == 0 {
-                                    let x = &tokens[start_idx..=idx];
-                                    udts.push(x);
-                                    break;
-                                }
-                            }
-                            _ => {},
-                        }
-                        idx += 1;
-                    }
-                }
-                _ => {
-                    idx = next_idx;
-                }
-            }
-        }
-        else {
-            idx += 1;
-        }
-    }
-
-    udts
+    get_udts_with_lang(tokens, Language::C).into_iter().map(|m| m.tokens).collect()
 }
 
 pub fn get_defines<'a>(tokens: &'a Vec<Token>) -> Vec<&'a [Token<'a>]> {
@@ -578,86 +1895,346 @@ pub fn get_defines<'a>(tokens: &'a Vec<Token>) -> Vec<&'a [Token<'a>]> {
 
 /// Gets the name of the struct
 /// Ex) for `struct Point {...}`, this would return "Point"
-pub fn get_udt_name<'a>(tokens: &'a [Token]) -> &'a str {
+///
+/// A UDT's name sits right after either the `struct`/`enum`/`union`/`class`
+/// keyword (`struct Point {...}`) or its brace `Group` (`typedef struct {...} Point;`),
+/// whichever comes first — walking the token tree finds both without needing
+/// to track brace depth by hand. Returns a [`LexError`] instead of panicking
+/// on a malformed UDT, since `tokens` is a slice `get_udts` already carved
+/// out with no accompanying `SourceMap`, the returned error's `span` is `None`.
+pub fn get_udt_name<'a>(tokens: &'a [Token]) -> std::result::Result<&'a str, LexError> {
+    let malformed = |message: &str| LexError {
+        kind: LexErrorKind::MalformedMacro,
+        message: message.to_string(),
+        span: None,
+    };
+
     if tokens.len() < 3 {
-        unreachable!("Token string is not a valid user defined type definition");
+        return Err(malformed("Token string is not a valid user defined type definition"));
     }
 
-    let mut idx = 0;
-    let mut num_unclosed_braces = 0;
-    
-    while idx < tokens.len() {
-        match tokens[idx] {
-            Token::Object("struct") |
-            Token::Object("enum") |
-            Token::Object("union") => {
-                let next_idx = idx + next_non_whitespace_token(&tokens[idx..]);
-
-                if next_idx + 1 >= tokens.len() {
-                    unreachable!("Invalid UDT (1)");
+    let tree = build_token_tree(tokens, None)
+        .map_err(|_| malformed("Token string is not a valid user defined type definition (unbalanced delimiters)"))?;
+
+    let mut i = 0;
+    while i < tree.len() {
+        let name_after = match &tree[i] {
+            TokenTree::Leaf(Token::Object("struct" | "enum" | "union" | "class"), _) => true,
+            TokenTree::Group(g) if g.delimiter == Delimiter::Brace => true,
+            _ => false,
+        };
+
+        if name_after {
+            let next = i + next_non_whitespace_tree(&tree[i..]);
+            if let Some(TokenTree::Leaf(Token::Object(obj), _)) = tree.get(next) {
+                return Ok(*obj);
+            }
+        }
+        i += 1;
+    }
+
+    Err(malformed("Invalid UDT (end)"))
+}
+
+/// Passing the below list to this function would return `3` (gets the next
+/// node, not the current one): `[node-curr, whitespace, whitespace, node-next]`.
+#[inline]
+fn next_non_whitespace_tree(tree: &[TokenTree<'_>]) -> usize {
+    let mut idx = 1;
+    while idx < tree.len()
+        && matches!(tree[idx], TokenTree::Leaf(Token::Space | Token::Tab | Token::NewLine | Token::Comment(_), _))
+    {
+        idx += 1;
+    }
+    idx
+}
+
+/// A UDT found by [`get_udts_with_lang`] in [`Language::Cpp`] mode, paired
+/// with the `::`-joined chain of enclosing `namespace` blocks it was found
+/// in (`None` for one at file scope). [`get_udts`] (C mode) has no notion of
+/// this, since C has no namespaces.
+#[derive(Debug, Clone)]
+pub struct UdtMatch<'a> {
+    pub namespace: Option<String>,
+    pub tokens: &'a [Token<'a>],
+}
+
+/// Walks a `<` starting at `tree[start]` to the `>` that closes it, treating
+/// a merged [`Token::ShiftRight`] as closing two nesting levels at once —
+/// the standard trick for disambiguating `vector<vector<int>>`'s trailing
+/// `>>` (which `assemble_operators` has already coalesced into one token by
+/// the time this runs) from the real `>>` shift operator. Bails out (`None`)
+/// on a `;` before the angle brackets balance, since that means this wasn't
+/// a template parameter list after all.
+fn skip_template_angle(tree: &[TokenTree<'_>], start: usize) -> Option<usize> {
+    let mut depth: i32 = 0;
+    let mut i = start;
+    while i < tree.len() {
+        match &tree[i] {
+            TokenTree::Leaf(Token::LessThan, _) => depth += 1,
+            TokenTree::Leaf(Token::GreaterThan, _) => {
+                depth -= 1;
+                if depth <= 0 {
+                    return Some(i + 1);
                 }
-                if let Token::Object(obj) = tokens[next_idx] {
-                    return obj;
+            }
+            TokenTree::Leaf(Token::ShiftRight, _) => {
+                depth -= 2;
+                if depth <= 0 {
+                    return Some(i + 1);
                 }
             }
-            Token::OpenCurlyBrace => num_unclosed_braces += 1,
-            Token::CloseCurlyBrace => {
-                if num_unclosed_braces == 0 {
-                    unreachable!("Invalid UDT (unmatched close curly brace)");
+            TokenTree::Leaf(Token::Semicolon, _) => return None,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// [`get_udts`], extended for [`Language::Cpp`]: recognizes `class` as a
+/// fourth struct-like keyword alongside `struct`/`union`/`enum`, skips a
+/// `template<...>` prefix (via [`skip_template_angle`]) before checking for
+/// one, and descends into `namespace Name { ... }` bodies (unlike plain
+/// `get_udts`, which only walks the top-level tree) to find UDTs nested
+/// inside, qualifying each with the namespace chain it was found in. In
+/// [`Language::C`] mode this returns exactly what [`get_udts`] does, just
+/// wrapped in [`UdtMatch`] with `namespace: None`.
+pub fn get_udts_with_lang<'a>(tokens: &'a Vec<Token>, lang: Language) -> Vec<UdtMatch<'a>> {
+    let mut udts = vec![];
+    if tokens.len() < 3 {
+        return udts;
+    }
+    let Ok(tree) = build_token_tree(tokens, None) else {
+        return udts;
+    };
+    collect_udts(tokens, &tree, lang, None, &mut udts);
+    udts
+}
+
+fn collect_udts<'a>(
+    tokens: &'a [Token<'a>],
+    tree: &[TokenTree<'a>],
+    lang: Language,
+    namespace: Option<&str>,
+    out: &mut Vec<UdtMatch<'a>>,
+) {
+    let struct_keywords: &[&str] = if lang == Language::Cpp {
+        &["struct", "union", "enum", "class"]
+    } else {
+        &["struct", "union", "enum"]
+    };
+
+    let mut i = 0;
+    while i < tree.len() {
+        let TokenTree::Leaf(Token::Object(obj), _) = &tree[i] else {
+            i += 1;
+            continue;
+        };
+        let obj = *obj;
+
+        if lang == Language::Cpp && obj == "namespace" {
+            let name_idx = i + next_non_whitespace_tree(&tree[i..]);
+            let body_idx = name_idx + next_non_whitespace_tree(&tree[name_idx..]);
+            if let (Some(TokenTree::Leaf(Token::Object(ns_name), _)), Some(TokenTree::Group(g))) =
+                (tree.get(name_idx), tree.get(body_idx))
+            {
+                if g.delimiter == Delimiter::Brace {
+                    let qualified = match namespace {
+                        Some(outer) => format!("{outer}::{ns_name}"),
+                        None => ns_name.to_string(),
+                    };
+                    collect_udts(tokens, &g.contents, lang, Some(&qualified), out);
+                    i = body_idx + 1;
+                    continue;
                 }
+            }
+            i += 1;
+            continue;
+        }
 
-                num_unclosed_braces -= 1;
+        let is_template = lang == Language::Cpp && obj == "template";
+        if !matches!(obj, "typedef") && !is_template && !struct_keywords.contains(&obj) {
+            i += 1;
+            continue;
+        }
 
-                if num_unclosed_braces == 0 {
-                    let next_idx = idx + next_non_whitespace_token(&tokens[idx..]);
-                    if next_idx + 1 >= tokens.len() {
-                        unreachable!("Invalid UDT (2)");
-                    }
+        let start = i;
+        let mut kw_idx = i;
+
+        if obj == "typedef" {
+            kw_idx += next_non_whitespace_tree(&tree[i..]);
+        } else if is_template {
+            // `template<...> class Foo { ... };` — skip past the angle-bracket
+            // parameter list (disambiguating its `>`/`>>` from comparison/shift
+            // operators via `skip_template_angle`) to reach the real keyword.
+            let angle_idx = i + next_non_whitespace_tree(&tree[i..]);
+            let Some(after) = (match tree.get(angle_idx) {
+                Some(TokenTree::Leaf(Token::LessThan, _)) => skip_template_angle(tree, angle_idx),
+                _ => None,
+            }) else {
+                i += 1;
+                continue;
+            };
+            kw_idx = after;
+            while matches!(
+                tree.get(kw_idx),
+                Some(TokenTree::Leaf(Token::Space | Token::Tab | Token::NewLine | Token::Comment(_), _))
+            ) {
+                kw_idx += 1;
+            }
+        }
 
-                    if let Token::Object(obj) = tokens[next_idx] {
-                        return obj;
-                    }
+        let is_struct_like = matches!(
+            tree.get(kw_idx),
+            Some(TokenTree::Leaf(Token::Object(k), _)) if struct_keywords.contains(k)
+        );
+        if !is_struct_like {
+            i = kw_idx + 1;
+            continue;
+        }
+
+        let (end_tree_idx, end_idx) = match scan_udt_body(tokens, tree, kw_idx) {
+            Some(found) => found,
+            None => {
+                i = kw_idx + 1;
+                continue;
+            }
+        };
+
+        let start_idx = tree[start].start_idx();
+        out.push(UdtMatch { namespace: namespace.map(str::to_string), tokens: &tokens[start_idx..=end_idx] });
+        i = end_tree_idx + 1;
+    }
+}
+
+/// The body of a UDT starting at `tree[kw_idx]` (the `struct`/`union`/`enum`/
+/// `class` keyword itself) is the next brace [`Group`] at this nesting level
+/// plus the `;` that must follow it, or a bare `;` for a forward declaration
+/// with no body — shared by [`get_udts`] and [`collect_udts`]. Returns the
+/// tree index of that closing `Group`/`;` (so the caller can resume scanning
+/// right after it) paired with its flat index into `tokens` (the UDT's end).
+fn scan_udt_body(tokens: &[Token], tree: &[TokenTree<'_>], kw_idx: usize) -> Option<(usize, usize)> {
+    let mut j = kw_idx + 1;
+    while j < tree.len() {
+        match &tree[j] {
+            TokenTree::Group(g) if g.delimiter == Delimiter::Brace => {
+                let mut k = g.close_idx + 1;
+                while k < tokens.len() && tokens[k] != Token::Semicolon {
+                    k += 1;
                 }
+                return if k < tokens.len() { Some((j, k)) } else { None };
             }
-            _ => {}
+            TokenTree::Leaf(Token::Semicolon, flat_idx) => return Some((j, *flat_idx)),
+            _ => j += 1,
         }
-        idx += 1;
     }
+    None
+}
 
-    unreachable!("Invalid UDT (end)");
+/// `get_udt_name` plus the `::`-joined chain of enclosing `namespace` blocks
+/// a C++ [`UdtMatch`] (from [`get_udts_with_lang`]) was found in, e.g.
+/// `Outer::Inner` — the same qualifier a consumer would need to write to
+/// name the type from outside its namespace. Falls back to the bare name
+/// when the match has no `namespace` (C mode, or a UDT at file scope).
+pub fn get_udt_qualified_name<'a>(m: &UdtMatch<'a>) -> std::result::Result<String, LexError> {
+    let name = get_udt_name(m.tokens)?;
+    Ok(match &m.namespace {
+        Some(ns) => format!("{ns}::{name}"),
+        None => name.to_string(),
+    })
 }
 
-/// Gets the name of the define statement
-/// Ex) for `#define FOO 42`, this would return "FOO"
-pub fn get_define_name<'a>(tokens: &'a [Token]) -> &'a str {
+/// A parsed `#define`, distinguishing an object-like macro (`#define FOO 42`
+/// — `params: None`, `body` is everything after `FOO`) from a function-like
+/// one (`#define MAX(a,b) ((a)>(b)?(a):(b))` — `params: Some(["a", "b"])`,
+/// `body` is everything after the closing `)`). The distinguishing rule is
+/// C's own: a `(` sitting immediately after the macro name, with no
+/// intervening whitespace, opens a parameter list rather than the body.
+#[derive(Debug, Clone)]
+pub struct MacroDef<'a> {
+    pub name: &'a str,
+    pub params: Option<Vec<&'a str>>,
+    pub body: &'a [Token<'a>],
+}
+
+/// Parses a `#define` token range (as returned by `get_defines`, so already
+/// spanning any `\`-continued lines) into a [`MacroDef`]. Returns a
+/// [`LexError`] instead of panicking on a malformed define, same as
+/// `get_define_name`/`get_udt_name` (no `SourceMap` is available for an
+/// already carved-out slice, so the error's `span` is `None`).
+pub fn parse_macro_def<'a>(tokens: &'a [Token]) -> std::result::Result<MacroDef<'a>, LexError> {
+    let malformed = |n: u8| LexError {
+        kind: LexErrorKind::MalformedMacro,
+        message: format!("Token string is not a valid define macro ({})", n),
+        span: None,
+    };
+
     if tokens.len() < 5 || tokens[0] != Token::HashTag {
-        unreachable!("Token string is not a valid define macro (1)");
+        return Err(malformed(1));
+    }
+
+    let mut i = 1;
+    while i < tokens.len() && tokens[i] != Token::Object("define") {
+        i += 1;
+    }
+    if i >= tokens.len() {
+        return Err(malformed(2));
     }
+    i += 1;
 
-    let mut define_seen = false;
+    while matches!(tokens.get(i), Some(Token::Space | Token::Tab)) {
+        i += 1;
+    }
+    let Some(Token::Object(name)) = tokens.get(i).copied() else {
+        return Err(malformed(3));
+    };
+    i += 1;
 
-    for &t in &tokens[1..] {
-        match t {
-            Token::Object("define") => {
-                if define_seen {
-                    unreachable!("Token string is not a valid define macro (2)");
+    let params = if tokens.get(i) == Some(&Token::OpenParen) {
+        i += 1;
+        let mut names = vec![];
+        loop {
+            match tokens.get(i) {
+                Some(Token::Space | Token::Tab) => i += 1,
+                Some(Token::Comma) => i += 1,
+                Some(Token::Object(p)) => {
+                    names.push(*p);
+                    i += 1;
                 }
-                define_seen = true;
-            }
-            Token::Object(obj) => {
-                if define_seen {
-                    return obj;
+                Some(Token::Ellipsis) => {
+                    names.push("...");
+                    i += 1;
                 }
-                else {
-                    unreachable!("Token string is not a valid define macro (3)");
+                Some(Token::CloseParen) => {
+                    i += 1;
+                    break;
                 }
+                _ => return Err(malformed(4)),
             }
-            _ => {}
         }
-    }
-
+        Some(names)
+    } else {
+        None
+    };
+
+    Ok(MacroDef {
+        name,
+        params,
+        body: &tokens[i..],
+    })
+}
 
-    unreachable!("Token string is not a valid define macro (4)");
+/// Gets the name of the define statement
+/// Ex) for `#define FOO 42`, this would return "FOO"
+///
+/// Returns a [`LexError`] instead of panicking on a malformed define (no
+/// `SourceMap` is available for a slice already carved out by `get_defines`,
+/// so the returned error's `span` is `None`). A thin wrapper over
+/// `parse_macro_def` for callers that only need the name, not the full
+/// `MacroDef`.
+pub fn get_define_name<'a>(tokens: &'a [Token]) -> std::result::Result<&'a str, LexError> {
+    parse_macro_def(tokens).map(|m| m.name)
 }
 
 /// Updates `idx` to point to the next token specified. If the
@@ -688,16 +2265,391 @@ fn skip_to_oneof(tokens: &[Token], targets: &[Token], idx: &mut usize) {
 }
 
 
-/// Passing the below list to this function would return `3` (gets the next token, not the current token)
-/// `[object-token-curr, whitespace, whitespace, object-token-next]`
-#[inline]
-fn next_non_whitespace_token(tokens: &[Token]) -> usize {
-    let mut idx = 1;
-    while idx < tokens.len() && matches!(tokens[idx], Token::Space | Token::Tab | Token::NewLine | Token::Comment(_)) {
-        idx += 1;
+/// Maps a macro name to its parsed definition, as collected by a caller from
+/// `get_defines`/`parse_macro_def`. Passed in to [`expand_macros`].
+pub type MacroTable<'a> = HashMap<&'a str, MacroDef<'a>>;
+
+/// A token carrying Prosser's hide set: the macro names that must not be
+/// re-expanded at this token, so a macro invocation that (directly or
+/// through another macro) expands back to its own name is emitted verbatim
+/// ("painted blue") instead of recursing forever. Internal bookkeeping only
+/// — [`expand_macros`]'s public signature stays in terms of plain `Token`.
+#[derive(Debug, Clone)]
+struct HideToken<'a> {
+    token: Token<'a>,
+    hide_set: HashSet<&'a str>,
+}
+
+fn macro_error(message: String) -> LexError {
+    LexError {
+        kind: LexErrorKind::MalformedMacro,
+        message,
+        span: None,
     }
+}
 
-    idx
+/// `Box::leak`s a freshly computed string so it can be held by a `Token<'a>`
+/// for any `'a` (including `'static`) — needed for `#`-stringized and
+/// `##`-pasted tokens, whose text doesn't exist anywhere in the original
+/// source buffer `Token`'s other variants borrow from.
+fn intern(s: &str) -> &'static str {
+    Box::leak(s.to_string().into_boxed_str())
+}
+
+/// Unions `hide_set` into every token in `tokens`, the final step of
+/// Prosser's `subst`: applied uniformly across the whole substituted output,
+/// including tokens that already picked up their own hide set from a nested
+/// expansion of a macro argument.
+fn hide_all<'a>(tokens: &mut [HideToken<'a>], hide_set: &HashSet<&'a str>) {
+    for t in tokens.iter_mut() {
+        t.hide_set.extend(hide_set.iter().copied());
+    }
+}
+
+fn intersect<'a>(a: &HashSet<&'a str>, b: &HashSet<&'a str>) -> HashSet<&'a str> {
+    a.intersection(b).copied().collect()
+}
+
+/// The plain text `tokens` would render as, ignoring hide sets — used to
+/// build the text `#` stringizes and `##` pastes.
+fn raw_text(tokens: &[HideToken]) -> String {
+    let plain: Vec<Token> = tokens.iter().map(|t| t.token).collect();
+    Token::tokens_to_string(&plain)
+}
+
+/// Wraps `text` in a `"..."` string literal, escaping `"` and `\` the way a
+/// `#`-stringized macro argument must be, per C's stringizing rule.
+fn escape_for_stringize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.trim().chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+/// Concatenates the spellings of `##`'s two operands and re-lexes the
+/// result, per C's token-pasting rule. Errors if the pasted text doesn't
+/// re-lex into exactly one token.
+fn paste_tokens(lhs: &str, rhs: &str) -> std::result::Result<Token<'static>, LexError> {
+    let combined: &'static str = intern(&format!("{}{}", lhs, rhs));
+
+    let (tokens, _source_map) = tokenize(combined)
+        .map_err(|_| macro_error(format!("`##` pasted \"{}\" is not a valid token", combined)))?;
+
+    let mut real = tokens
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Space | Token::Tab | Token::NewLine));
+
+    let Some(first) = real.next() else {
+        return Err(macro_error(format!("`##` pasted \"{}\" produced no token", combined)));
+    };
+    if real.next().is_some() {
+        return Err(macro_error(format!(
+            "`##` pasted \"{}\" produced more than one token",
+            combined
+        )));
+    }
+
+    Ok(first)
+}
+
+/// Whether `body[i]` is (modulo whitespace/comments) immediately followed by
+/// a `##` paste operator — there's no dedicated token variant for `##` (see
+/// the `Token` enum above), so it's detected as two adjacent `HashTag`s.
+fn followed_by_paste(body: &[Token], i: usize) -> bool {
+    let mut j = i + 1;
+    while matches!(body.get(j), Some(Token::Space | Token::Tab | Token::NewLine | Token::Comment(_))) {
+        j += 1;
+    }
+    matches!(body.get(j), Some(Token::HashTag)) && matches!(body.get(j + 1), Some(Token::HashTag))
+}
+
+/// Resolves a macro body identifier to its formal-parameter index, treating
+/// `__VA_ARGS__` as a reference to the trailing `...` parameter.
+fn param_idx(params: &[&str], name: &str) -> Option<usize> {
+    if name == "__VA_ARGS__" {
+        return params.iter().position(|p| *p == "...");
+    }
+    params.iter().position(|p| *p == name)
+}
+
+/// Substitutes `params`/`args` into a macro `body`, per Prosser's `subst`:
+/// `#` stringizes a parameter's unexpanded actual argument, `##` pastes the
+/// unexpanded spellings of its two operands, a plain parameter reference is
+/// replaced by its actual argument *after* that argument is fully macro-expanded
+/// on its own, and everything else copies through verbatim. `hide_set` (the
+/// caller's hide set plus the macro's own name, already computed by
+/// `expand_queue`) is unioned into every resulting token at the end.
+fn subst<'a>(
+    body: &'a [Token<'a>],
+    params: &[&'a str],
+    args: &[Vec<HideToken<'a>>],
+    hide_set: &HashSet<&'a str>,
+    defines: &MacroTable<'a>,
+) -> std::result::Result<Vec<HideToken<'a>>, LexError> {
+    let has_params = !params.is_empty();
+    let mut out: Vec<HideToken<'a>> = Vec::new();
+    let mut i = 0;
+
+    while i < body.len() {
+        if body[i] == Token::HashTag && body.get(i + 1) == Some(&Token::HashTag) {
+            let mut j = i + 2;
+            while matches!(body.get(j), Some(Token::Space | Token::Tab | Token::NewLine | Token::Comment(_))) {
+                j += 1;
+            }
+            let Some(&rhs_tok) = body.get(j) else {
+                return Err(macro_error("`##` has no right-hand operand".to_string()));
+            };
+            let rhs_text = match rhs_tok {
+                Token::Object(name) if param_idx(params, name).is_some() => {
+                    raw_text(&args[param_idx(params, name).unwrap()])
+                }
+                _ => Token::tokens_to_string(&[rhs_tok]),
+            };
+
+            while matches!(
+                out.last().map(|t| t.token),
+                Some(Token::Space | Token::Tab | Token::NewLine | Token::Comment(_))
+            ) {
+                out.pop();
+            }
+            let Some(lhs) = out.pop() else {
+                return Err(macro_error("`##` has no left-hand operand".to_string()));
+            };
+            let lhs_text = Token::tokens_to_string(&[lhs.token]);
+
+            let pasted = paste_tokens(&lhs_text, &rhs_text)?;
+            out.push(HideToken {
+                token: pasted,
+                hide_set: HashSet::new(),
+            });
+
+            i = j + 1;
+            continue;
+        }
+
+        if has_params && body[i] == Token::HashTag {
+            let mut j = i + 1;
+            while matches!(body.get(j), Some(Token::Space | Token::Tab | Token::NewLine | Token::Comment(_))) {
+                j += 1;
+            }
+            if let Some(Token::Object(name)) = body.get(j) {
+                if let Some(p) = param_idx(params, name) {
+                    let literal = intern(&escape_for_stringize(&raw_text(&args[p])));
+                    out.push(HideToken {
+                        token: Token::Literal(literal),
+                        hide_set: HashSet::new(),
+                    });
+                    i = j + 1;
+                    continue;
+                }
+            }
+        }
+
+        if has_params {
+            if let Token::Object(name) = body[i] {
+                if let Some(p) = param_idx(params, name) {
+                    let arg = &args[p];
+                    if followed_by_paste(body, i) {
+                        out.push(HideToken {
+                            token: Token::Object(intern(&raw_text(arg))),
+                            hide_set: HashSet::new(),
+                        });
+                    } else {
+                        let mut queue: VecDeque<HideToken> = arg.iter().cloned().collect();
+                        out.extend(expand_queue(&mut queue, defines)?);
+                    }
+                    i += 1;
+                    continue;
+                }
+            }
+        }
+
+        out.push(HideToken {
+            token: body[i],
+            hide_set: HashSet::new(),
+        });
+        i += 1;
+    }
+
+    hide_all(&mut out, hide_set);
+    Ok(out)
+}
+
+/// Whether `queue` is (modulo whitespace/comments) about to open a
+/// function-like macro's argument list, and if so how many leading tokens —
+/// the whitespace plus the `(` itself — to drop to get past it. `None` means
+/// a non-whitespace token other than `(` came first, so the macro name isn't
+/// being called here and must pass through unexpanded.
+fn peek_open_paren(queue: &VecDeque<HideToken>) -> Option<usize> {
+    for (i, t) in queue.iter().enumerate() {
+        match t.token {
+            Token::Space | Token::Tab | Token::NewLine | Token::Comment(_) => continue,
+            Token::OpenParen => return Some(i + 1),
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Collects a function-like macro invocation's actual-argument token
+/// sequences from `queue` (already past the invocation's own `(`), splitting
+/// on top-level (paren-depth 0) commas — per the request, `#define`'s own
+/// rule: once `params`'s fixed (non-`...`) slots are filled for a variadic
+/// macro, further top-level commas stay inside the trailing
+/// `...`/`__VA_ARGS__` argument rather than starting a new one, since that's
+/// exactly what `__VA_ARGS__` expands to. Returns the collected arguments
+/// alongside the closing paren's hide set, needed by the caller's hide-set
+/// intersection rule. Errors if `queue` runs out before the matching `)`.
+fn collect_args<'a>(
+    queue: &mut VecDeque<HideToken<'a>>,
+    params: &[&'a str],
+) -> std::result::Result<(Vec<Vec<HideToken<'a>>>, HashSet<&'a str>), LexError> {
+    let variadic = params.last() == Some(&"...");
+    let fixed_count = if variadic { params.len() - 1 } else { params.len() };
+
+    let mut args: Vec<Vec<HideToken<'a>>> = vec![Vec::new()];
+    let mut depth: usize = 0;
+
+    let close_hide_set = loop {
+        let Some(tok) = queue.pop_front() else {
+            return Err(macro_error("Function-like macro invocation never closed".to_string()));
+        };
+        match tok.token {
+            Token::OpenParen => {
+                depth += 1;
+                args.last_mut().unwrap().push(tok);
+            }
+            Token::CloseParen if depth == 0 => {
+                break tok.hide_set;
+            }
+            Token::CloseParen => {
+                depth -= 1;
+                args.last_mut().unwrap().push(tok);
+            }
+            Token::Comma if depth == 0 && (!variadic || args.len() <= fixed_count) => {
+                args.push(Vec::new());
+            }
+            _ => {
+                args.last_mut().unwrap().push(tok);
+            }
+        }
+    };
+
+    if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+        args.clear();
+    }
+
+    // Catch an arity mismatch here rather than let `subst` index `args[p]`
+    // out of bounds for a formal parameter the call site never supplied
+    // (e.g. `#define MAX(a, b) ...` invoked as `MAX(x)`).
+    if variadic {
+        if args.len() < fixed_count {
+            return Err(macro_error(format!(
+                "macro invocation has {} argument(s), expected at least {}",
+                args.len(),
+                fixed_count
+            )));
+        }
+        // A call that supplies only the fixed arguments still gets an empty
+        // `...` slot, so `__VA_ARGS__` always has an argument to index.
+        if args.len() == fixed_count {
+            args.push(Vec::new());
+        }
+    } else if args.len() != params.len() {
+        return Err(macro_error(format!(
+            "macro invocation has {} argument(s), expected {}",
+            args.len(),
+            params.len()
+        )));
+    }
+
+    Ok((args, close_hide_set))
+}
+
+/// Rescans `queue`, expanding every macro invocation it finds per Prosser's
+/// hide-set algorithm, until nothing is left to rescan. A replacement's
+/// tokens are pushed back onto the front of `queue` (not appended to the
+/// output) so they themselves get a chance to expand further before anything
+/// after them does.
+fn expand_queue<'a>(
+    queue: &mut VecDeque<HideToken<'a>>,
+    defines: &MacroTable<'a>,
+) -> std::result::Result<Vec<HideToken<'a>>, LexError> {
+    let mut output = Vec::new();
+
+    while let Some(tok) = queue.pop_front() {
+        let Token::Object(name) = tok.token else {
+            output.push(tok);
+            continue;
+        };
+        let Some(def) = defines.get(name) else {
+            output.push(tok);
+            continue;
+        };
+        if tok.hide_set.contains(name) {
+            // Painted blue: already expanding this macro further up the
+            // call chain, so emit it verbatim to guarantee termination.
+            output.push(tok);
+            continue;
+        }
+
+        match &def.params {
+            None => {
+                let mut hide_set = tok.hide_set.clone();
+                hide_set.insert(name);
+                let replaced = subst(def.body, &[], &[], &hide_set, defines)?;
+                for t in replaced.into_iter().rev() {
+                    queue.push_front(t);
+                }
+            }
+            Some(params) => {
+                let Some(paren_len) = peek_open_paren(queue) else {
+                    // Not followed by `(` here, so this isn't a call —
+                    // pass the name through unexpanded.
+                    output.push(tok);
+                    continue;
+                };
+                for _ in 0..paren_len {
+                    queue.pop_front();
+                }
+                let (args, close_hide_set) = collect_args(queue, params)?;
+                let mut hide_set = intersect(&tok.hide_set, &close_hide_set);
+                hide_set.insert(name);
+                let replaced = subst(def.body, params, &args, &hide_set, defines)?;
+                for t in replaced.into_iter().rev() {
+                    queue.push_front(t);
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Expands every macro invocation in `tokens` against `defines`, per
+/// Prosser's hide-set algorithm (see `expand_queue`/`subst`), supporting both
+/// object-like and function-like macros (including variadic ones via
+/// `...`/`__VA_ARGS__`).
+pub fn expand_macros<'a>(
+    tokens: &'a [Token<'a>],
+    defines: &MacroTable<'a>,
+) -> std::result::Result<Vec<Token<'a>>, LexError> {
+    let mut queue: VecDeque<HideToken<'a>> = tokens
+        .iter()
+        .map(|&token| HideToken {
+            token,
+            hide_set: HashSet::new(),
+        })
+        .collect();
+
+    let expanded = expand_queue(&mut queue, defines)?;
+    Ok(expanded.into_iter().map(|t| t.token).collect())
 }
 
 #[cfg(test)]
@@ -709,7 +2661,7 @@ mod lexer_tests {
     #[test]
     fn test_get_defines() {
         let s = fs::read_to_string("tests/lexer-define.c").unwrap();
-        let tokens = tokenize(&s).unwrap();
+        let (tokens, _source_map) = tokenize(&s).unwrap();
 
         let defines = get_defines(&tokens);
 
@@ -730,7 +2682,7 @@ mod lexer_tests {
     #[test]
     fn test_get_udts() {
         let s = fs::read_to_string("tests/lexer-UDT.c").unwrap();
-        let tokens = tokenize(&s).unwrap();
+        let (tokens, _source_map) = tokenize(&s).unwrap();
 
         let defines = get_udts(&tokens);
 
@@ -746,13 +2698,13 @@ mod lexer_tests {
     #[test]
     fn test_get_define_name() {
         let s = fs::read_to_string("tests/lexer-define.c").unwrap();
-        let tokens = tokenize(&s).unwrap();
+        let (tokens, _source_map) = tokenize(&s).unwrap();
 
         let defines = get_defines(&tokens);
 
         let mut names = vec![];
         for &d in &defines {
-            names.push(get_define_name(d));
+            names.push(get_define_name(d).unwrap());
         }
 
         assert_eq!(
@@ -770,13 +2722,13 @@ mod lexer_tests {
     #[test]
     fn test_get_udt_name() {
         let s = fs::read_to_string("tests/lexer-UDT.c").unwrap();
-        let tokens = tokenize(&s).unwrap();
+        let (tokens, _source_map) = tokenize(&s).unwrap();
 
         let structs = get_udts(&tokens);
 
         let mut names = vec![];
         for &d in &structs {
-            names.push(get_udt_name(d));
+            names.push(get_udt_name(d).unwrap());
         }
 
         let mut dump = "".to_string();
@@ -787,4 +2739,186 @@ mod lexer_tests {
 
         fs::write("tests/lexer.test_get_udt_name.log", format!("{}", dump)).unwrap();
     }
+
+    /// `tokenize` dispatches on whichever of `"`/`'`/`//`/`/*` it sees first
+    /// and then jumps straight past the whole literal/comment in one
+    /// `find_len_*` call, so a `//`-shaped byte run inside a string can't be
+    /// mistaken for the start of a line comment, and a `"`/`'`-shaped byte
+    /// run inside a comment can't be mistaken for the start of a literal.
+    #[test]
+    fn test_string_and_comment_scanning_dont_cross_streams() {
+        let (tokens, _) = tokenize(r#"const char *url = "http://example.com"; // trailing"#).unwrap();
+        assert!(tokens.contains(&Token::Literal("\"http://example.com\"")));
+        assert!(tokens.iter().any(|t| matches!(t, Token::Comment(c) if c.starts_with("//"))));
+
+        let (tokens, _) = tokenize("/* a \"quote\" and a 'char' inside a comment */ int x;").unwrap();
+        assert!(tokens.iter().any(|t| matches!(t, Token::Comment(c) if c.contains("\"quote\""))));
+        assert!(!tokens.contains(&Token::Literal("\"quote\"")));
+    }
+
+    /// `get_udts`/`get_fn_def` both resolve a definition's closing delimiter
+    /// by walking `build_token_tree`'s tree rather than hand-counting brace
+    /// depth, so a nested UDT/function-pointer-typed field inside the body
+    /// can't be mistaken for the enclosing definition's own close brace.
+    #[test]
+    fn test_nested_braces_resolve_via_token_tree() {
+        // A struct nested inside another's body can't be mistaken for the
+        // outer struct's own closing brace — `Outer`'s body is a single
+        // `Group` in the tree, not a brace count an inner `{`/`}` pair could
+        // throw off.
+        let src = "struct Outer { struct Inner { int x; } inner; int y; };";
+        let (tokens, _) = tokenize(src).unwrap();
+        let udts = get_udts(&tokens);
+        assert_eq!(udts.len(), 1, "get_udts only walks the top-level tree, not into nested Groups");
+        assert_eq!(get_udt_name(udts[0]).unwrap(), "Outer");
+        assert!(Token::tokens_to_string(udts[0]).trim_end().ends_with("};"));
+
+        let src = "int add(int (*cb)(int, int), int a, int b) { return cb(a, b); }";
+        let (tokens, _) = tokenize(src).unwrap();
+        let fn_defs = get_fn_def(&tokens);
+        assert_eq!(fn_defs.len(), 1, "the nested (*cb)(int, int) parens shouldn't split the signature");
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let cases: &[(&str, &str)] = &[
+            ("1.5e-3f", "1.5e-3f"),
+            ("0xFFu", "0xFFu"),
+            ("0b1010", "0b1010"),
+            ("100UL", "100UL"),
+            ("0x1p3", "0x1p3"),
+            (".5f", ".5f"),
+            ("100'000", "100'000"),
+        ];
+
+        for &(src, expected) in cases {
+            let (tokens, _) = tokenize(src).unwrap();
+            assert_eq!(tokens, vec![Token::Number(expected)], "tokenizing {:?}", src);
+        }
+
+        // A plain `.` that isn't followed by a digit stays `Token::Period`.
+        let (tokens, _) = tokenize("a.b").unwrap();
+        assert!(tokens.contains(&Token::Period));
+        assert!(!tokens.iter().any(|t| matches!(t, Token::Number(_))));
+    }
+
+    /// `collect_args` must reject an arity mismatch with a `LexError`
+    /// rather than let `subst` index `args[p]` out of bounds for a formal
+    /// parameter the call site never supplied.
+    #[test]
+    fn test_expand_macros_arity_mismatch() {
+        let s = "#define MAX(a, b) ((a) > (b) ? (a) : (b))\nMAX(x)\n";
+        let (tokens, _source_map) = tokenize(s).unwrap();
+
+        let mut table = MacroTable::new();
+        for def_tokens in get_defines(&tokens) {
+            let def = parse_macro_def(def_tokens).unwrap();
+            table.insert(def.name, def);
+        }
+
+        let err = expand_macros(&tokens, &table).unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::MalformedMacro);
+    }
+
+    #[test]
+    fn test_expand_macros() {
+        let s = fs::read_to_string("tests/lexer-macros.c").unwrap();
+        let (tokens, _source_map) = tokenize(&s).unwrap();
+
+        let mut table = MacroTable::new();
+        for def_tokens in get_defines(&tokens) {
+            let def = parse_macro_def(def_tokens).unwrap();
+            table.insert(def.name, def);
+        }
+
+        let expanded = expand_macros(&tokens, &table).unwrap();
+
+        fs::write(
+            "tests/lexer.test_expand_macros.log",
+            Token::tokens_to_string(&expanded),
+        )
+        .unwrap();
+    }
+
+    /// Differential harness: re-tokenizing `tests/lexer-macros.c` must
+    /// produce the exact same wire-format dump as the committed golden
+    /// file. A diverging run prints the first mismatched line with
+    /// surrounding context rather than a raw `assert_eq!` of two
+    /// thousand-token strings.
+    #[test]
+    fn test_wire_format_matches_golden_file() {
+        let src = fs::read_to_string("tests/lexer-macros.c").unwrap();
+        let (tokens, source_map) = tokenize(&src).unwrap();
+        let actual = tokens_to_wire(&tokens, &source_map);
+
+        let expected = fs::read_to_string("tests/lexer-macros.c.wire").unwrap();
+
+        if let Some(diff) = diff_wire(&expected, &actual) {
+            panic!("{diff}");
+        }
+    }
+
+    #[test]
+    fn test_wire_round_trip() {
+        let src = fs::read_to_string("tests/lexer-macros.c").unwrap();
+        let (tokens, source_map) = tokenize(&src).unwrap();
+        let wire = tokens_to_wire(&tokens, &source_map);
+
+        let parsed = from_wire(&wire);
+        assert_eq!(parsed.len(), tokens.len());
+
+        for (tok, (i, wire_tok)) in tokens.iter().zip(parsed.iter().enumerate()) {
+            assert_eq!(wire_tok.kind, tok.wire_kind());
+            assert_eq!(
+                wire_tok.spelling,
+                escape_wire(&Token::tokens_to_string(std::slice::from_ref(tok)))
+            );
+            let span = source_map.span(i);
+            assert_eq!(wire_tok.start_byte, span.start_byte);
+            assert_eq!(wire_tok.end_byte, span.end_byte);
+        }
+    }
+
+    /// `get_udts_with_lang` in `Language::Cpp` mode must recognize `class`
+    /// and `template<...> class`, descend into (possibly nested) `namespace`
+    /// bodies unlike plain `get_udts`, and qualify each UDT it finds there
+    /// with the `::`-joined namespace chain.
+    #[test]
+    fn test_cpp_udts() {
+        let s = fs::read_to_string("tests/lexer-cpp-udts.cpp").unwrap();
+        let (tokens, _source_map) = tokenize_lang(&s, Language::Cpp).unwrap();
+
+        let udts = get_udts_with_lang(&tokens, Language::Cpp);
+        let names: Vec<String> = udts.iter().map(|m| get_udt_qualified_name(m).unwrap()).collect();
+
+        assert_eq!(names, vec!["outer::Point", "outer::inner::Box", "Color"]);
+
+        // In plain C mode, none of this is reachable: `class`/`namespace`
+        // aren't recognized and nested bodies aren't walked at all.
+        let c_udts = get_udts(&tokens);
+        assert_eq!(
+            c_udts.iter().map(|d| Token::tokens_to_string(d)).collect::<Vec<_>>(),
+            vec![Token::tokens_to_string(udts.last().unwrap().tokens)],
+            "C mode should only ever find the top-level `enum Color`"
+        );
+    }
+
+    /// A raw string literal's delimiter-sensitive body (parens, quotes,
+    /// whatever it likes) must survive as one `Literal` token in C++ mode,
+    /// the same text a normal string literal couldn't hold without escaping.
+    #[test]
+    fn test_cpp_raw_string_literal() {
+        let src = r####"const char *s = R"delim(a "quote" and a (paren) and no escape: \n)delim";"####;
+        let (tokens, _) = tokenize_lang(src, Language::Cpp).unwrap();
+        assert!(tokens.contains(&Token::Literal(
+            r####"R"delim(a "quote" and a (paren) and no escape: \n)delim""####
+        )));
+
+        // The exact same source in C mode (no R-prefix recognition) instead
+        // lets ordinary object scanning run straight through the `"` (it's
+        // not a symbol character), merging "R" and the delimiter into one
+        // object up to the first `(`.
+        let (tokens, _) = tokenize(src).unwrap();
+        assert!(tokens.contains(&Token::Object("R\"delim")));
+    }
 }
@@ -1,6 +1,8 @@
 pub mod lexer_c;
 
 use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
 
 use anyhow::{anyhow, Result};
 
@@ -13,12 +15,12 @@ pub fn merge_defines<'a>(
     let mut dst_set = HashSet::new();
 
     for &tokens in dst.iter() {
-        let s = lexer_c::get_define_name(tokens);
+        let s = lexer_c::get_define_name(tokens)?;
         dst_set.insert(s);
     }
 
     for &tokens in src.iter() {
-        let s = lexer_c::get_define_name(tokens);
+        let s = lexer_c::get_define_name(tokens)?;
         if dst_set.contains(&s) {
             return Err(anyhow!("Duplicate #define definitions for {}", s));
         }
@@ -59,12 +61,12 @@ pub fn merge_udts<'a>(
     let mut dst_set = HashSet::new();
 
     for &tokens in dst.iter() {
-        let s = lexer_c::get_udt_name(tokens);
+        let s = lexer_c::get_udt_name(tokens)?;
         dst_set.insert(s);
     }
 
     for &tokens in src.iter() {
-        let s = lexer_c::get_udt_name(tokens);
+        let s = lexer_c::get_udt_name(tokens)?;
         if dst_set.contains(&s) {
             return Err(anyhow!("Duplicate struct definitions for {}", s));
         }
@@ -142,3 +144,58 @@ pub fn filter_out_includes<'a>(
         })
         .collect()
 }
+
+/// Returns the raw `<...>` spelling of a system include (e.g. `<stdio.h>`),
+/// or `None` if `include` is a `"..."` local include instead. `include` is a
+/// full `#include ...` token slice, as returned by [`lexer_c::get_includes`].
+fn system_include_spelling(include: &[lexer_c::Token]) -> Option<String> {
+    let lt_idx = include
+        .iter()
+        .position(|t| *t == lexer_c::Token::LessThan)?;
+    Some(lexer_c::Token::tokens_to_string(&include[lt_idx..]).trim().to_string())
+}
+
+/// Builds an umbrella header for `ingot_name` that `#include`s each header in
+/// `headers` (already written to `header_dir`, named relative to it) in the
+/// given order, wrapped in an include guard and an `extern "C"` block so C++
+/// consumers get C linkage from a single `#include`. Each header's own
+/// `<...>` system includes are hoisted above the per-header includes,
+/// deduplicated and kept in first-seen order.
+pub fn gen_umbrella_header(ingot_name: &str, header_dir: &Path, headers: &[String]) -> Result<String> {
+    let guard = format!("{}_H", ingot_name.to_uppercase().replace('-', "_"));
+
+    let mut system_includes = vec![];
+    let mut seen = HashSet::new();
+
+    for header in headers {
+        let code = fs::read_to_string(header_dir.join(header))?;
+        let (tokens, _source_map) = lexer_c::tokenize(&code)?;
+        for inc in lexer_c::get_includes(&tokens) {
+            if let Some(spelling) = system_include_spelling(inc) {
+                if seen.insert(spelling.clone()) {
+                    system_includes.push(spelling);
+                }
+            }
+        }
+    }
+
+    let mut contents = String::new();
+    contents.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+
+    for inc in &system_includes {
+        contents.push_str(&format!("#include {}\n", inc));
+    }
+    if !system_includes.is_empty() {
+        contents.push('\n');
+    }
+
+    contents.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    for header in headers {
+        contents.push_str(&format!("#include \"{}\"\n", header));
+    }
+    contents.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+
+    contents.push_str(&format!("#endif // {}\n", guard));
+
+    Ok(contents)
+}
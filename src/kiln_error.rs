@@ -0,0 +1,163 @@
+//! The crate-wide typed error, used where call sites need to `match` on what
+//! went wrong (e.g. to decide whether a failure is worth retrying) rather
+//! than just display a message. Mirrors the `thiserror`-derived
+//! [`crate::packaging::package_manager::PkgError`] pattern: each variant
+//! carries its own typed context and `#[source]`s the error it wraps, so
+//! `{}` renders a readable message while `source()` still exposes the full
+//! chain for anything that wants it (e.g. `anyhow`'s `{:?}`).
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+pub type KilnResult<T> = Result<T, KilnError>;
+
+#[derive(Debug, Error)]
+pub enum KilnError {
+    #[error("file not found: `{}`", path.display())]
+    FileNotFound {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("permission denied: `{}`", path.display())]
+    PermissionDenied {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("I/O error on `{}`: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{}", render_toml_parse_error(path, source_text, source))]
+    TomlParse {
+        path: PathBuf,
+        source_text: String,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("failed to parse `{}` as JSON: {source}", path.display())]
+    JsonParseError {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("failed to parse `{}` as YAML: {source}", path.display())]
+    YamlParseError {
+        path: PathBuf,
+        #[source]
+        source: serde_yaml::Error,
+    },
+
+    #[error("{0}")]
+    Config(String),
+
+    #[error("{0}")]
+    Unknown(String),
+}
+
+impl KilnError {
+    /// Classifies an `io::Error` into [`KilnError::FileNotFound`],
+    /// [`KilnError::PermissionDenied`], or the generic [`KilnError::Io`] by
+    /// `source.kind()`, so a caller's `match` on the resulting variant is
+    /// meaningful for retry/skip logic instead of string-sniffing a message.
+    pub fn from_io(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        let path = path.into();
+        match source.kind() {
+            std::io::ErrorKind::NotFound => KilnError::FileNotFound { path, source },
+            std::io::ErrorKind::PermissionDenied => KilnError::PermissionDenied { path, source },
+            _ => KilnError::Io { path, source },
+        }
+    }
+
+    pub fn toml_parse(path: impl Into<PathBuf>, source_text: impl Into<String>, source: toml::de::Error) -> Self {
+        KilnError::TomlParse { path: path.into(), source_text: source_text.into(), source }
+    }
+
+    pub fn json_parse(path: impl Into<PathBuf>, source: serde_json::Error) -> Self {
+        KilnError::JsonParseError { path: path.into(), source }
+    }
+
+    pub fn yaml_parse(path: impl Into<PathBuf>, source: serde_yaml::Error) -> Self {
+        KilnError::YamlParseError { path: path.into(), source }
+    }
+
+    pub fn config(msg: impl Into<String>) -> Self {
+        KilnError::Config(msg.into())
+    }
+
+    pub fn unknown(msg: impl Into<String>) -> Self {
+        KilnError::Unknown(msg.into())
+    }
+}
+
+/// Builds a `KilnError::$variant(format!($fmt, $args...))` for the
+/// string-carrying variants (`Config`, `Unknown`). The typed variants that
+/// carry a source error go through their own constructor instead (e.g.
+/// [`KilnError::from_io`]), since a formatted message alone can't supply one.
+#[macro_export]
+macro_rules! format_err {
+    ($variant:ident, $($arg:tt)*) => {
+        $crate::kiln_error::KilnError::$variant(format!($($arg)*))
+    };
+}
+
+/// [`format_err!`], immediately returned as an `Err` from the current function.
+#[macro_export]
+macro_rules! bail {
+    ($variant:ident, $($arg:tt)*) => {
+        return Err($crate::format_err!($variant, $($arg)*))
+    };
+}
+
+/// Renders a `toml::de::Error` as a line/column-annotated snippet of
+/// `source_text` (the file at `path`, already read), the way `toml`'s own
+/// `Display` impl does for a bare parse error, but anchored to the actual
+/// file so the user sees which file and which line failed without having to
+/// cross-reference a bare "invalid type" message.
+pub fn render_toml_parse_error(path: &Path, source_text: &str, err: &toml::de::Error) -> String {
+    let Some(span) = err.span() else {
+        return format!("failed to parse `{}` as TOML: {}", path.display(), err.message());
+    };
+
+    let lines: Vec<&str> = source_text.split('\n').collect();
+    let mut offset = 0;
+    let mut line_no = 0;
+    let mut col = span.start;
+    for (i, line) in lines.iter().enumerate() {
+        let line_len = line.len() + 1; // +1 for the '\n' this split ate
+        if offset + line_len > span.start {
+            line_no = i;
+            col = span.start - offset;
+            break;
+        }
+        offset += line_len;
+    }
+
+    const CONTEXT: usize = 2;
+    let ctx_start = line_no.saturating_sub(CONTEXT);
+    let ctx_end = (line_no + CONTEXT + 1).min(lines.len());
+
+    let mut out = format!(
+        "failed to parse `{}` as TOML, line {}, column {}\n",
+        path.display(),
+        line_no + 1,
+        col + 1
+    );
+    for (i, line) in lines.iter().enumerate().take(ctx_end).skip(ctx_start) {
+        out.push_str(&format!("{:>4} | {}\n", i + 1, line));
+        if i == line_no {
+            out.push_str(&format!("     | {}^\n", " ".repeat(col)));
+        }
+    }
+    out.push_str(err.message());
+
+    out
+}
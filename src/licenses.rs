@@ -0,0 +1,220 @@
+/// A curated SPDX identifier -> license text table, offered to `kiln init`/`kiln new`
+/// via `--license <id>` so users don't have to hand-copy license text.
+pub struct LicenseInfo {
+    pub spdx_id: &'static str,
+    pub name: &'static str,
+    pub text: &'static str,
+}
+
+pub fn find(spdx_id: &str) -> Option<&'static LicenseInfo> {
+    LICENSES.iter().find(|l| l.spdx_id.eq_ignore_ascii_case(spdx_id))
+}
+
+pub const LICENSES: &[LicenseInfo] = &[
+    LicenseInfo {
+        spdx_id: "MIT",
+        name: "MIT License",
+        text: "MIT License\n\n\
+Copyright (c) <year> <copyright holders>\n\n\
+Permission is hereby granted, free of charge, to any person obtaining a copy \
+of this software and associated documentation files (the \"Software\"), to deal \
+in the Software without restriction, including without limitation the rights \
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell \
+copies of the Software, and to permit persons to whom the Software is \
+furnished to do so, subject to the following conditions:\n\n\
+The above copyright notice and this permission notice shall be included in all \
+copies or substantial portions of the Software.\n\n\
+THE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR \
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, \
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE \
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER \
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, \
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE \
+SOFTWARE.\n",
+    },
+    LicenseInfo {
+        spdx_id: "BSD-2-Clause",
+        name: "BSD 2-Clause \"Simplified\" License",
+        text: "BSD 2-Clause License\n\n\
+Copyright (c) <year>, <copyright holders>\n\n\
+Redistribution and use in source and binary forms, with or without \
+modification, are permitted provided that the following conditions are met:\n\n\
+1. Redistributions of source code must retain the above copyright notice, this \
+   list of conditions and the following disclaimer.\n\n\
+2. Redistributions in binary form must reproduce the above copyright notice, \
+   this list of conditions and the following disclaimer in the documentation \
+   and/or other materials provided with the distribution.\n\n\
+THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS \"AS IS\" \
+AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE \
+IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE \
+DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE \
+FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL \
+DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR \
+SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER \
+CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, \
+OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE \
+OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.\n",
+    },
+    LicenseInfo {
+        spdx_id: "BSD-3-Clause",
+        name: "BSD 3-Clause \"New\" or \"Revised\" License",
+        text: "BSD 3-Clause License\n\n\
+Copyright (c) <year>, <copyright holders>\n\n\
+Redistribution and use in source and binary forms, with or without \
+modification, are permitted provided that the following conditions are met:\n\n\
+1. Redistributions of source code must retain the above copyright notice, this \
+   list of conditions and the following disclaimer.\n\n\
+2. Redistributions in binary form must reproduce the above copyright notice, \
+   this list of conditions and the following disclaimer in the documentation \
+   and/or other materials provided with the distribution.\n\n\
+3. Neither the name of the copyright holder nor the names of its contributors \
+   may be used to endorse or promote products derived from this software \
+   without specific prior written permission.\n\n\
+THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS \"AS IS\" \
+AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE \
+IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE \
+DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE \
+FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL \
+DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR \
+SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER \
+CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, \
+OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE \
+OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.\n",
+    },
+    LicenseInfo {
+        spdx_id: "ISC",
+        name: "ISC License",
+        text: "ISC License\n\n\
+Copyright (c) <year>, <copyright holders>\n\n\
+Permission to use, copy, modify, and/or distribute this software for any \
+purpose with or without fee is hereby granted, provided that the above \
+copyright notice and this permission notice appear in all copies.\n\n\
+THE SOFTWARE IS PROVIDED \"AS IS\" AND THE AUTHOR DISCLAIMS ALL WARRANTIES WITH \
+REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF MERCHANTABILITY \
+AND FITNESS. IN NO EVENT SHALL THE AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, \
+INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM \
+LOSS OF USE, DATA OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR \
+OTHER TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR \
+PERFORMANCE OF THIS SOFTWARE.\n",
+    },
+    LicenseInfo {
+        spdx_id: "Apache-2.0",
+        name: "Apache License 2.0",
+        text: "Apache License\nVersion 2.0, January 2004\n\
+http://www.apache.org/licenses/\n\n\
+Copyright <year> <copyright holders>\n\n\
+Licensed under the Apache License, Version 2.0 (the \"License\"); you may not \
+use this file except in compliance with the License. You may obtain a copy of \
+the License at\n\n\
+    http://www.apache.org/licenses/LICENSE-2.0\n\n\
+Unless required by applicable law or agreed to in writing, software \
+distributed under the License is distributed on an \"AS IS\" BASIS, WITHOUT \
+WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied. See the \
+License for the specific language governing permissions and limitations under \
+the License.\n\n\
+See <http://www.apache.org/licenses/LICENSE-2.0> for the full text, including \
+definitions and the terms governing redistribution, contribution, and patent \
+grants.\n",
+    },
+    LicenseInfo {
+        spdx_id: "MPL-2.0",
+        name: "Mozilla Public License 2.0",
+        text: "Mozilla Public License, version 2.0\n\n\
+This Source Code Form is subject to the terms of the Mozilla Public License, \
+v. 2.0. If a copy of the MPL was not distributed with this file, you can \
+obtain one at http://mozilla.org/MPL/2.0/.\n\n\
+See <http://mozilla.org/MPL/2.0/> for the full text of the license, including \
+the definitions and terms governing \"Covered Software\", \"Larger Works\", and \
+distribution under both the MPL and secondary licenses.\n",
+    },
+    LicenseInfo {
+        spdx_id: "GPL-2.0",
+        name: "GNU General Public License v2.0",
+        text: "GNU GENERAL PUBLIC LICENSE\nVersion 2, June 1991\n\n\
+Copyright (C) 1989, 1991 Free Software Foundation, Inc.\n\
+51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA\n\n\
+Everyone is permitted to copy and distribute verbatim copies of this license \
+document, but changing it is not allowed.\n\n\
+This program is free software; you can redistribute it and/or modify it under \
+the terms of the GNU General Public License as published by the Free Software \
+Foundation; either version 2 of the License, or (at your option) any later \
+version.\n\n\
+This program is distributed in the hope that it will be useful, but WITHOUT \
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS \
+FOR A PARTICULAR PURPOSE. See the GNU General Public License for more \
+details.\n\n\
+See <https://www.gnu.org/licenses/old-licenses/gpl-2.0.html> for the full text \
+of the license terms (sections 0-12) that this notice summarizes.\n",
+    },
+    LicenseInfo {
+        spdx_id: "GPL-3.0",
+        name: "GNU General Public License v3.0",
+        text: "GNU GENERAL PUBLIC LICENSE\nVersion 3, 29 June 2007\n\n\
+Copyright (C) 2007 Free Software Foundation, Inc. <https://fsf.org/>\n\n\
+Everyone is permitted to copy and distribute verbatim copies of this license \
+document, but changing it is not allowed.\n\n\
+This program is free software: you can redistribute it and/or modify it under \
+the terms of the GNU General Public License as published by the Free Software \
+Foundation, either version 3 of the License, or (at your option) any later \
+version.\n\n\
+This program is distributed in the hope that it will be useful, but WITHOUT \
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS \
+FOR A PARTICULAR PURPOSE. See the GNU General Public License for more \
+details.\n\n\
+See <https://www.gnu.org/licenses/gpl-3.0.html> for the full text of the \
+license terms (sections 0-17) that this notice summarizes.\n",
+    },
+    LicenseInfo {
+        spdx_id: "LGPL-2.1",
+        name: "GNU Lesser General Public License v2.1",
+        text: "GNU LESSER GENERAL PUBLIC LICENSE\nVersion 2.1, February 1999\n\n\
+Copyright (C) 1991, 1999 Free Software Foundation, Inc.\n\
+51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA\n\n\
+Everyone is permitted to copy and distribute verbatim copies of this license \
+document, but changing it is not allowed.\n\n\
+This library is free software; you can redistribute it and/or modify it under \
+the terms of the GNU Lesser General Public License as published by the Free \
+Software Foundation; either version 2.1 of the License, or (at your option) \
+any later version.\n\n\
+This library is distributed in the hope that it will be useful, but WITHOUT \
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS \
+FOR A PARTICULAR PURPOSE. See the GNU Lesser General Public License for more \
+details.\n\n\
+See <https://www.gnu.org/licenses/old-licenses/lgpl-2.1.html> for the full \
+text of the license terms.\n",
+    },
+    LicenseInfo {
+        spdx_id: "LGPL-3.0",
+        name: "GNU Lesser General Public License v3.0",
+        text: "GNU LESSER GENERAL PUBLIC LICENSE\nVersion 3, 29 June 2007\n\n\
+Copyright (C) 2007 Free Software Foundation, Inc. <https://fsf.org/>\n\n\
+Everyone is permitted to copy and distribute verbatim copies of this license \
+document, but changing it is not allowed.\n\n\
+This version of the GNU Lesser General Public License incorporates the terms \
+and conditions of version 3 of the GNU General Public License, supplemented \
+by the additional permissions in the full LGPLv3 text.\n\n\
+See <https://www.gnu.org/licenses/lgpl-3.0.html> for the full text of the \
+license terms.\n",
+    },
+    LicenseInfo {
+        spdx_id: "Unlicense",
+        name: "The Unlicense",
+        text: "This is free and unencumbered software released into the public domain.\n\n\
+Anyone is free to copy, modify, publish, use, compile, sell, or distribute \
+this software, either in source code form or as a compiled binary, for any \
+purpose, commercial or non-commercial, and by any means.\n\n\
+In jurisdictions that recognize copyright laws, the author or authors of this \
+software dedicate any and all copyright interest in the software to the \
+public domain. We make this dedication for the benefit of the public at \
+large and to the detriment of our heirs and successors. We intend this \
+dedication to be an overt act of relinquishment in perpetuity of all present \
+and future rights to this software under copyright law.\n\n\
+THE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR \
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, \
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE \
+AUTHORS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN \
+ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION \
+WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.\n\n\
+For more information, please refer to <https://unlicense.org>\n",
+    },
+];
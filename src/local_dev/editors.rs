@@ -7,7 +7,7 @@ use crate::{
 use serde_json::Value;
 use serde_yaml::{Mapping, Value as YmlValue};
 use std::{fs, path::Path};
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 
 pub fn handle_editor_includes(config: &Config, proj_dir: impl AsRef<Path>) -> Result<()> {
     let local_dev_file = proj_dir.as_ref().join(DEV_ENV_CFG_FILE);
@@ -16,7 +16,14 @@ pub fn handle_editor_includes(config: &Config, proj_dir: impl AsRef<Path>) -> Re
         return Ok(());
     }
 
-    let local_config = fs::read_to_string(local_dev_file)?;
+    let local_config = fs::read_to_string(&local_dev_file).map_err(|e| match e.kind() {
+        std::io::ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied reading dev config file `{}`",
+            local_dev_file.display()
+        ),
+        _ => anyhow::Error::new(e)
+            .context(format!("Failed to read dev config file `{}`", local_dev_file.display())),
+    })?;
     let local_config: DevEnvConfig = toml::from_str(&local_config)?;
 
     if local_config.editor.is_none() {
@@ -55,7 +62,7 @@ fn set_include(
 ) -> Result<()> {
     let editor = match dev_config.editor {
         Some(e) => e,
-        None => return Err(anyhow!("Dev config file doesn't exist")),
+        None => bail!("Dev config file doesn't exist"),
     };
     match editor {
         EditorType::VsCode => {
@@ -64,10 +71,7 @@ fn set_include(
         EditorType::Helix | EditorType::Zed | EditorType::NeoVim => {
             set_include_clangd(includes, proj_dir)?;
         }
-        _ => {
-            let msg = format!("Support for `{:?}` is not yet supported", editor);
-            return Err(anyhow!(msg));
-        }
+        _ => bail!("Support for `{:?}` is not yet supported", editor),
     }
 
     Ok(())
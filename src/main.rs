@@ -2,13 +2,17 @@ mod build_sys;
 mod cli;
 mod config;
 mod constants;
+mod depgraph;
 mod header_gen;
+mod kiln_error;
+mod licenses;
 mod local_dev;
 mod packaging;
+mod target;
 mod testing;
 mod utils;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Parser;
 use config::Config;
 use constants::{CONFIG_FILE, DEV_ENV_CFG_FILE, PACKAGE_DIR, SEPARATOR};
@@ -17,6 +21,7 @@ use local_dev::{dev_env_config, editors};
 use packaging::package_manager::{self, PkgError};
 use std::{env, fs, io::Write, path::Path, process, time};
 use strum::IntoEnumIterator;
+use testing::harness;
 use testing::safety;
 use utils::Language;
 
@@ -24,11 +29,42 @@ use utils::Language;
 async fn main() {
     let cli_args: cli::CliCommand;
     let raw_cli_args = std::env::args().collect::<Vec<String>>();
+
+    // Best-effort config load just for alias expansion; an invalid/missing
+    // Kiln.toml here just means "no aliases available" rather than a hard error.
+    let alias_cfg = Config::from(&env::current_dir().unwrap().join(CONFIG_FILE)).ok();
+    let raw_cli_args = match cli::expand_aliases(alias_cfg.as_ref(), &raw_cli_args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            process::exit(1);
+        }
+    };
+
     if raw_cli_args.len() < 2 {
         // Let the program fail and have Clap display it's help message
-        cli_args = cli::CliCommand::parse();
+        cli_args = cli::CliCommand::parse_from(&raw_cli_args);
     } 
     else if matches!(raw_cli_args[1].as_str(), "run" | "build" | "build-trace") {
+        // `--memcheck` is consumed here (rather than left for clap) since this
+        // branch does its own positional parsing instead of going through clap.
+        let memcheck = raw_cli_args.iter().any(|a| a == "--memcheck");
+        let target = raw_cli_args
+            .iter()
+            .position(|a| a == "--target")
+            .and_then(|idx| raw_cli_args.get(idx + 1).cloned());
+        let mut raw_cli_args: Vec<String> = raw_cli_args
+            .into_iter()
+            .filter(|a| a != "--memcheck")
+            .collect();
+        if let Some(idx) = raw_cli_args.iter().position(|a| a == "--target") {
+            // Removes both `--target` and its value.
+            raw_cli_args.remove(idx);
+            if idx < raw_cli_args.len() {
+                raw_cli_args.remove(idx);
+            }
+        }
+
         let mut profile = "--debug".to_string();
         let mut args = vec![];
         if raw_cli_args.len() >= 3
@@ -42,7 +78,7 @@ async fn main() {
             // Extracts passthrough CLI arguments (kiln run)
             assert!([2_usize, 3_usize].contains(&idx));
             args = raw_cli_args[(idx + 1)..].to_vec();
-        } 
+        }
         else {
             // verify structure of CLI arguments
             if !(raw_cli_args.len() <= 3) {
@@ -51,21 +87,21 @@ async fn main() {
             }
         }
         cli_args = cli::CliCommand {
-            command: cli::Commands::new(&raw_cli_args[1], &profile, args),
+            command: cli::Commands::new(&raw_cli_args[1], &profile, args, memcheck, target),
         }
-    } 
+    }
     else {
-        cli_args = cli::CliCommand::parse();
+        cli_args = cli::CliCommand::parse_from(&raw_cli_args);
     }
 
     let cwd = env::current_dir().unwrap();
     let config = Config::from(&cwd.join(CONFIG_FILE));
 
     match cli_args.command {
-        cli::Commands::Init { language } => {
+        cli::Commands::Init { language, license } => {
             let cwd = env::current_dir().unwrap();
 
-            if let Err(e) = build_sys::create_project(&cwd, language) {
+            if let Err(e) = build_sys::create_project(&cwd, language, license.as_deref()) {
                 println!("An error occurred while creating the project:\n{}", e);
                 process::exit(1);
             }
@@ -73,6 +109,7 @@ async fn main() {
         cli::Commands::New {
             proj_name,
             language,
+            license,
         } => {
             let mut target_dir = env::current_dir().unwrap();
             target_dir.push(proj_name);
@@ -82,11 +119,11 @@ async fn main() {
             }
             fs::create_dir(&target_dir).unwrap();
 
-            if let Err(e) = build_sys::create_project(&target_dir, language) {
+            if let Err(e) = build_sys::create_project(&target_dir, language, license.as_deref()) {
                 println!("An error occurred while creating the project:\n{}", e);
             }
         }
-        cli::Commands::GenHeaders { args } => {
+        cli::Commands::GenHeaders { args, umbrella } => {
             if let Err(e) = build_sys::validate_proj_repo(cwd.as_path()) {
                 println!("{}", e);
                 process::exit(1);
@@ -98,20 +135,28 @@ async fn main() {
                 process::exit(0);
             }
 
-            if let Err(err) = handle_gen_headers(&config, args) {
+            if let Err(err) = handle_gen_headers(&config, args, umbrella) {
                 println!("An error occurred while generating header files:\n{}", err);
                 process::exit(1);
             }
         }
-        cli::Commands::Add { dep_uri } => {
+        cli::Commands::Add { dep_uri, allow_build_scripts, offline } => {
             if let Err(e) = build_sys::validate_proj_repo(cwd.as_path()) {
                 println!("{}", e);
                 process::exit(1);
             }
             let mut config = config.unwrap();
 
-            let (owner, proj_name) = package_manager::parse_github_uri(&dep_uri).unwrap();
-            let res = package_manager::resolve_adding_package(&mut config, owner, proj_name, None);
+            let (host, owner, proj_name) = package_manager::parse_repo_uri(&dep_uri).unwrap();
+            let res = package_manager::resolve_adding_package(
+                &mut config,
+                host,
+                owner,
+                proj_name,
+                None,
+                allow_build_scripts,
+                offline,
+            );
 
             if let Err(err) = res.await {
                 match &err {
@@ -121,11 +166,11 @@ async fn main() {
                             dbg!(e);
                             eprintln!("Request timed out, please check internet connection");
                         } else {
-                            eprintln!("An unknown error occurred:\n{}", err);
+                            eprintln!("An unknown error occurred:\n{:?}", err);
                         }
                     }
                     _ => {
-                        eprintln!("An unknown error occurred:\n{}", err);
+                        eprintln!("An unknown error occurred:\n{:?}", err);
                     }
                 }
                 std::process::exit(1);
@@ -142,7 +187,19 @@ async fn main() {
             fs::remove_dir_all(&pkg_dir).unwrap();
             fs::create_dir(&pkg_dir).unwrap();
         }
-        cli::Commands::Build { profile } => {
+        cli::Commands::Package { list } => {
+            if let Err(e) = build_sys::validate_proj_repo(cwd.as_path()) {
+                println!("{}", e);
+                process::exit(1);
+            }
+            let config = config.unwrap();
+
+            if let Err(e) = packaging::publish::package_ingot(&config, list) {
+                eprintln!("An error occurred while packaging the ingot:\n{}", e);
+                process::exit(1);
+            }
+        }
+        cli::Commands::Build { profile, target } => {
             if let Err(e) = build_sys::validate_proj_repo(cwd.as_path()) {
                 println!("{}", e);
                 process::exit(1);
@@ -155,8 +212,9 @@ async fn main() {
                 process::exit(1);
             }
 
+            let target = resolve_target(target.as_deref());
             for &b_type in config.project.build_type.iter() {
-                if let Err(e) = handle_build(&profile, &config, b_type) {
+                if let Err(e) = handle_build(&profile, &config, b_type, &[], &target) {
                     eprintln!(
                         "An error occurred while building the project (build mode {:?}):\n{}",
                         b_type, e
@@ -168,6 +226,8 @@ async fn main() {
         cli::Commands::Run {
             profile,
             args,
+            memcheck,
+            target,
         } => {
             if let Err(e) = build_sys::validate_proj_repo(cwd.as_path()) {
                 println!("{}", e);
@@ -186,19 +246,23 @@ async fn main() {
                 eprintln!("An error occurred during static analysis:\n{}", e);
                 process::exit(1);
             }
-            if let Err(e) = handle_build(&profile, &config, config::BuildType::Exe) {
+
+            let target = resolve_target(target.as_deref());
+            let backend = memcheck.then(build_sys::detect_memcheck_backend);
+            let extra_flags = memcheck_compile_flags(backend);
+            if let Err(e) = handle_build(&profile, &config, config::BuildType::Exe, &extra_flags, &target) {
                 eprintln!("An error occurred while building the project:\n{}", e);
                 process::exit(1);
             }
 
-            let err = handle_execution(&profile, &config, &cwd, &args);
+            let err = handle_execution(&profile, &config, &cwd, &args, backend);
             if let Err(e) = err {
                 eprintln!("Code build successfully, but failed to execute:\n{}", e);
                 process::exit(1);
             }
-            
+
         }
-        cli::Commands::BuildTrace { profile } => {
+        cli::Commands::BuildTrace { profile, target } => {
             if let Err(e) = build_sys::validate_proj_repo(cwd.as_path()) {
                 println!("{}", e);
                 process::exit(1);
@@ -211,10 +275,11 @@ async fn main() {
                 process::exit(1);
             }
 
+            let target = resolve_target(target.as_deref());
             for &b_type in config.project.build_type.iter() {
                 println!("BuildType: {:?}", b_type);
 
-                let comp_cmd = build_compilation_cmd(&profile, &config, b_type);
+                let comp_cmd = build_compilation_cmd(&profile, &config, b_type, &[], &target);
 
                 match comp_cmd {
                     Ok(v) => {
@@ -231,7 +296,7 @@ async fn main() {
             }
 
         }
-        cli::Commands::Test { tests } => {
+        cli::Commands::Test { tests, bless, memcheck, jobs } => {
             if let Err(e) = build_sys::validate_proj_repo(cwd.as_path()) {
                 println!("{}", e);
                 process::exit(1);
@@ -242,34 +307,56 @@ async fn main() {
 
             if let Some(tests) = tests.as_ref() {
                 files_to_test.extend_from_slice(&tests);
-            } 
+            }
             else if let Ok(test_dir) = Path::new("tests").read_dir() {
                 for file in test_dir {
-                    if let Ok(file) = file {                           
+                    if let Ok(file) = file {
                         let filepath = file.path();
                         let filepath = filepath.to_str()
                             .unwrap();
                         files_to_test.push(filepath.to_string());
                     }
                 }
-            } 
+            }
             else {
                 eprintln!("unable to read test directory");
                 process::exit(1);
             }
 
-            let seperator = "=".repeat(40);
-            println!("\n\n");
+            let jobs = jobs.unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            });
+
+            println!("running {} tests ({} job(s))\n", files_to_test.len(), jobs);
 
-            for file in &files_to_test {
-                println!("{a}\n{b:?}\n{a}", a=seperator, b=file);
+            let outcomes = run_test_suite(&config, files_to_test, bless, memcheck, jobs).await;
 
-                let res = handle_tests("--debug", &config, file);
-                if let Err(err) = res {
-                    println!("{}", err);
+            let passed = outcomes.iter().filter(|o| o.passed).count();
+            let failed = outcomes.len() - passed;
+
+            if failed > 0 {
+                let seperator = "=".repeat(40);
+                println!("{}\nfailures:\n", seperator);
+                for outcome in outcomes.iter().filter(|o| !o.passed) {
+                    println!(
+                        "test `{}` ({:.2?}) ... FAILED\n{}\n",
+                        outcome.name, outcome.duration, outcome.message
+                    );
                 }
+                println!("{}", seperator);
+            }
+
+            println!(
+                "test result: {}. {} passed; {} failed",
+                if failed == 0 { "ok" } else { "FAILED" },
+                passed,
+                failed,
+            );
 
-                println!("{}\n\n\n", seperator);
+            if failed > 0 {
+                process::exit(1);
             }
         }
         cli::Commands::LocalDev { subcommand } => match subcommand {
@@ -336,7 +423,7 @@ fn handle_warnings(config: &Config) -> Result<Vec<safety::Warning>> {
         return Ok(vec![]);
     }
 
-    let warnings = safety::check_files(&config.project.language)?;
+    let warnings = safety::check_files(config, &config.project.language)?;
 
     for w in &warnings {
         utils::print_warning(
@@ -354,7 +441,22 @@ fn handle_warnings(config: &Config) -> Result<Vec<safety::Warning>> {
     Ok(warnings)
 }
 
-fn build_compilation_cmd(profile: &str, config: &Config, build_type: config::BuildType) -> Result<Vec<String>> {
+/// Resolves a `--target` CLI value (if any) to a [`target::Target`], falling
+/// back to the host kiln itself was built on when the user didn't cross-compile.
+fn resolve_target(triple: Option<&str>) -> target::Target {
+    match triple {
+        Some(triple) => target::Target::from_triple(triple),
+        None => target::Target::default_host(),
+    }
+}
+
+fn build_compilation_cmd(
+    profile: &str,
+    config: &Config,
+    build_type: config::BuildType,
+    extra_flags: &[String],
+    target: &target::Target,
+) -> Result<Vec<String>> {
     if !profile.starts_with("--") {
         eprintln!("Error: profile must start with `--`");
         process::exit(1);
@@ -371,7 +473,7 @@ fn build_compilation_cmd(profile: &str, config: &Config, build_type: config::Bui
     let mut link_file = vec![];
     build_sys::link_dep_files(&config, lang, &mut link_file)?;
     build_sys::link_proj_files(&config, &cwd, lang, &mut link_file)
-        .map_err(|err| anyhow!("Failed to link source files: {}", err))?;
+        .context("Failed to link source files")?;
 
     let link_lib = build_sys::link_sys_lib(&cwd);
     let opt_flags = build_sys::opt_flags(&profile, config).unwrap();
@@ -379,7 +481,7 @@ fn build_compilation_cmd(profile: &str, config: &Config, build_type: config::Bui
     let header_dirs = build_sys::link_dep_headers(&config)?;
     let so_dir = build_sys::link_dep_shared_obj(&cwd)?;
 
-    let compilation_cmd = build_sys::full_compilation_cmd(
+    let mut compilation_cmd = build_sys::full_compilation_cmd(
         config,
         &profile,
         &link_file,
@@ -388,13 +490,21 @@ fn build_compilation_cmd(profile: &str, config: &Config, build_type: config::Bui
         &so_dir,
         &opt_flags,
         build_type,
+        target,
     )?;
+    compilation_cmd.extend(extra_flags.iter().cloned());
 
     Ok(compilation_cmd)
 }
 
-fn handle_build(profile: &str, config: &Config, build_type: config::BuildType) -> Result<()> {
-    let compilation_cmd = build_compilation_cmd(profile, config, build_type)?;
+fn handle_build(
+    profile: &str,
+    config: &Config,
+    build_type: config::BuildType,
+    extra_flags: &[String],
+    target: &target::Target,
+) -> Result<()> {
+    let compilation_cmd = build_compilation_cmd(profile, config, build_type, extra_flags, target)?;
 
     #[cfg(debug_assertions)]
     {
@@ -432,14 +542,55 @@ fn handle_build(profile: &str, config: &Config, build_type: config::BuildType) -
         ));
     }
 
+    let is_library = matches!(
+        build_type,
+        config::BuildType::static_library | config::BuildType::dynamic_library
+    );
+    if is_library && config.emit_pc_file() {
+        let project_dir = env::current_dir().unwrap();
+        build_sys::generate_pkg_config_file(config, &project_dir, &build_dir)?;
+    }
+
     Ok(())
 }
 
+/// Extra compiler flags to pass when `--memcheck` picked the `Sanitizer` backend;
+/// empty for `Valgrind` (it wraps the already-built binary instead) or `None`.
+fn memcheck_compile_flags(backend: Option<build_sys::MemcheckBackend>) -> Vec<String> {
+    match backend {
+        Some(build_sys::MemcheckBackend::Sanitizer) => build_sys::SANITIZER_MEMCHECK_FLAGS
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Prepends `so_dirs` to `cmd`'s dynamic-loader search path env var (preserving
+/// whatever that variable was already set to), so a binary linked against shared
+/// dependencies can find them at runtime.
+fn set_dylib_search_path(cmd: &mut process::Command, so_dirs: &[String]) {
+    if so_dirs.is_empty() {
+        return;
+    }
+
+    let var = build_sys::dylib_env_var();
+    let mut paths: Vec<std::path::PathBuf> = so_dirs.iter().map(std::path::PathBuf::from).collect();
+    if let Some(existing) = env::var_os(var) {
+        paths.extend(env::split_paths(&existing));
+    }
+
+    if let Ok(joined) = env::join_paths(paths) {
+        cmd.env(var, joined);
+    }
+}
+
 fn handle_execution(
     profile: &str,
     config: &Config,
     project_dir: &Path,
     passthrough_args: &[String],
+    memcheck_backend: Option<build_sys::MemcheckBackend>,
 ) -> Result<()> {
     if !profile.starts_with("--") {
         return Err(anyhow!("Error: profile must start with `--`"));
@@ -454,13 +605,44 @@ fn handle_execution(
         return Err(anyhow!("Binary {:?} does not exist", bin_path));
     }
 
-    let output = process::Command::new(&bin_path)
-        .args(passthrough_args)
-        .stdin(process::Stdio::inherit())
+    let so_dir = build_sys::link_dep_shared_obj(project_dir)?;
+
+    let mut cmd = match memcheck_backend {
+        Some(build_sys::MemcheckBackend::Valgrind) => {
+            println!("running under memcheck backend: valgrind");
+            let argv = build_sys::wrap_with_valgrind(&bin_path, passthrough_args);
+            let mut cmd = process::Command::new(&argv[0]);
+            cmd.args(&argv[1..]);
+            cmd
+        }
+        Some(build_sys::MemcheckBackend::Sanitizer) => {
+            println!("running under memcheck backend: sanitizer (AddressSanitizer/UBSan)");
+            let mut cmd = process::Command::new(&bin_path);
+            cmd.args(passthrough_args);
+            cmd
+        }
+        None => {
+            let mut cmd = process::Command::new(&bin_path);
+            cmd.args(passthrough_args);
+            cmd
+        }
+    };
+    cmd.stdin(process::Stdio::inherit())
         .stdout(process::Stdio::inherit())
-        .stderr(process::Stdio::inherit())
+        .stderr(process::Stdio::inherit());
+    set_dylib_search_path(&mut cmd, &so_dir);
+
+    let output = cmd
         .output()
-        .map_err(|e| anyhow!("Failed to run {:?} binary: {}", bin_path, e))?;
+        .with_context(|| format!("Failed to run {:?} binary", bin_path))?;
+
+    if memcheck_backend == Some(build_sys::MemcheckBackend::Valgrind)
+        && output.status.code() == Some(build_sys::VALGRIND_ERROR_EXIT_CODE)
+    {
+        return Err(anyhow!(
+            "valgrind detected a memory error (see output above)"
+        ));
+    }
 
     if !output.status.success() {
         let code = output.status.code().unwrap_or(1);
@@ -470,7 +652,7 @@ fn handle_execution(
     Ok(())
 }
 
-fn handle_gen_headers(config: &Config, mut files: Option<Vec<String>>) -> Result<()> {
+fn handle_gen_headers(config: &Config, mut files: Option<Vec<String>>, umbrella: bool) -> Result<()> {
     let cwd = env::current_dir()?;
     let src_dir = config.get_src_dir();
     let inc_dir = config.get_include_dir();
@@ -478,6 +660,8 @@ fn handle_gen_headers(config: &Config, mut files: Option<Vec<String>>) -> Result
     let src_dir = cwd.join(src_dir);
     let inc_dir = cwd.join(inc_dir);
 
+    let mut generated_headers = vec![];
+
     files.as_mut().map(|v| {
         for i in 0..v.len() {
             let idx = v[i].rfind('/');
@@ -506,10 +690,10 @@ fn handle_gen_headers(config: &Config, mut files: Option<Vec<String>>) -> Result
             let header_name = format!("{}.h", raw_name);
 
             let code = fs::read_to_string(file.path())?;
-            let tokens = lexer_c::tokenize(&code)?;
+            let (tokens, _source_map) = lexer_c::tokenize(&code)?;
 
             let code_h = fs::read_to_string(inc_dir.join(&header_name)).unwrap_or("".to_string());
-            let tokens_h = lexer_c::tokenize(&code_h)?;
+            let (tokens_h, _source_map_h) = lexer_c::tokenize(&code_h)?;
 
             let mut defines_h = lexer_c::get_defines(&tokens_h);
             let mut udts_h = lexer_c::get_udts(&tokens_h);
@@ -581,12 +765,13 @@ fn handle_gen_headers(config: &Config, mut files: Option<Vec<String>>) -> Result
             headers.push_str(&format!("#endif // {}_H", raw_name.to_uppercase()));
 
             fs::write(inc_dir.join(&header_name), headers)?;
+            generated_headers.push(header_name.clone());
 
             // Remove definitions from original C file to avoid duplicates
             let mut exclude_tokens = udts;
             exclude_tokens.extend_from_slice(&defines);
 
-            let mut new_code = lexer_c::reconstruct_source(&tokens, &exclude_tokens);
+            let mut new_code = lexer_c::reconstruct_source(&tokens, &exclude_tokens, None);
 
             let header_inc_path = format!("\"../include/{}\"", &header_name);
 
@@ -599,6 +784,13 @@ fn handle_gen_headers(config: &Config, mut files: Option<Vec<String>>) -> Result
             fs::write(new_filepath, new_code).unwrap();
         }
     }
+
+    if umbrella {
+        let umbrella_name = format!("{}.h", config.project.name);
+        let contents = header_gen::gen_umbrella_header(&config.project.name, &inc_dir, &generated_headers)?;
+        fs::write(inc_dir.join(umbrella_name), contents)?;
+    }
+
     Ok(())
 }
 
@@ -616,21 +808,65 @@ async fn handle_check_installs(config: &Config) {
     }
 
     for i in not_installed {
-        package_manager::resolve_adding_package(&mut config, &i[0], &i[1], Some(&i[2]))
-            .await
-            .unwrap();
+        // Re-installing an already-accepted dependency never needs re-prompting
+        // for build scripts; it was already gated (or allowed) when first added.
+        package_manager::resolve_adding_package(
+            &mut config,
+            &i[0],
+            &i[1],
+            &i[2],
+            Some(&i[3]),
+            true,
+            false,
+        )
+        .await
+        .unwrap();
     }
 
     #[cfg(debug_assertions)]
     dbg!(timer.elapsed());
 }
 
-fn handle_tests(profile: &str, config: &Config, test_file: &str) -> Result<()> {
+/// Result of compiling and running a single test file, for `run_test_suite` to
+/// aggregate into a consolidated pass/fail summary.
+#[derive(Debug)]
+struct TestOutcome {
+    name: String,
+    passed: bool,
+    message: String,
+    duration: time::Duration,
+}
+
+/// Replaces each character outside `[a-zA-Z0-9]` with `_` (one `_` per
+/// character, runs are not collapsed), so a test's path can be embedded
+/// in a build output filename without colliding with another test's slash/dot.
+fn sanitize_test_slug(test_file: &str) -> String {
+    test_file
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Compiles and runs a single test, returning a human-readable `Ok` (pass) or
+/// `Err` (fail) message. Each test compiles into its own binary, keyed by a
+/// slug of its path, so `run_test_suite` can build tests concurrently without
+/// them clobbering each other's output in the shared `build/<profile>` dir.
+fn handle_tests(
+    profile: &str,
+    config: &Config,
+    test_file: &str,
+    bless: bool,
+    memcheck: bool,
+) -> Result<String> {
     if !profile.starts_with("--") {
         eprintln!("Error: profile must start with `--`");
         process::exit(1);
     }
 
+    let memcheck_backend = memcheck.then(build_sys::detect_memcheck_backend);
+
+    let directives = harness::parse_directives_from_file(test_file).unwrap_or_default();
+
     let cwd = env::current_dir().unwrap();
 
     let build_dir = cwd.join("build").join(&profile[2..]);
@@ -642,7 +878,7 @@ fn handle_tests(profile: &str, config: &Config, test_file: &str) -> Result<()> {
     let mut link_file = vec![];
     build_sys::link_dep_files(&config, lang, &mut link_file)?;
     build_sys::link_proj_files(&config, &cwd, lang, &mut link_file)
-        .map_err(|err| anyhow!("Failed to link source files: {}", err))?;
+        .context("Failed to link source files")?;
 
     let main_file = config.get_main_filepath();
     link_file = link_file
@@ -658,7 +894,7 @@ fn handle_tests(profile: &str, config: &Config, test_file: &str) -> Result<()> {
     let header_dirs = build_sys::link_dep_headers(&config)?;
     let so_dir = build_sys::link_dep_shared_obj(&cwd)?;
 
-    let compilation_cmd = build_sys::full_compilation_cmd(
+    let mut compilation_cmd = build_sys::full_compilation_cmd(
         config,
         &profile,
         &link_file,
@@ -668,6 +904,23 @@ fn handle_tests(profile: &str, config: &Config, test_file: &str) -> Result<()> {
         &opt_flags,
         config::BuildType::Exe,
     )?;
+    compilation_cmd.extend(directives.flags.iter().cloned());
+    compilation_cmd.extend(memcheck_compile_flags(memcheck_backend));
+
+    // `full_compilation_cmd` points its output at the shared per-profile binary;
+    // redirect it to a per-test path so concurrent test builds don't clobber it.
+    let default_bin_path = cwd.join("build").join(&profile[2..]).join(&config.project.name);
+    let bin_path = cwd
+        .join("build")
+        .join(&profile[2..])
+        .join(format!("{}-test-{}", config.project.name, sanitize_test_slug(test_file)));
+    let default_bin_str = default_bin_path.to_string_lossy().to_string();
+    let bin_str = bin_path.to_string_lossy().to_string();
+    for arg in compilation_cmd.iter_mut() {
+        if *arg == default_bin_str {
+            *arg = bin_str.clone();
+        }
+    }
 
     let output = process::Command::new(&compilation_cmd[0])
         .args(&compilation_cmd[1..])
@@ -676,28 +929,153 @@ fn handle_tests(profile: &str, config: &Config, test_file: &str) -> Result<()> {
         .stderr(process::Stdio::piped())
         .output()?;
 
-    if !output.status.success() {
-        let msg = String::from_utf8(output.stderr).unwrap_or("unknown stderr".to_string());
-        return Err(anyhow!("Compilation failed for `{}`:\n{}", test_file, msg));
+    let compile_stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+    let observation = harness::TestObservation {
+        compiled: output.status.success(),
+        compile_stderr,
+        exit_code: None,
+        stdout: String::new(),
+    };
+
+    if directives.mode() == harness::TestMode::CompileFail {
+        return match harness::check_expectation(&directives, &observation) {
+            Ok(()) => Ok("ok (failed to compile, as expected)".to_string()),
+            Err(msg) => Err(anyhow!("{}", msg)),
+        };
     }
 
-    let bin_path = cwd
-        .join("build")
-        .join(&profile[2..])
-        .join(&config.project.name);
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Compilation failed for `{}`:\n{}",
+            test_file,
+            observation.compile_stderr
+        ));
+    }
 
     if !bin_path.exists() {
         return Err(anyhow!("Binary {:?} does not exist", bin_path));
     }
 
-    let _output = process::Command::new(&bin_path)
-        .stdin(process::Stdio::inherit())
-        .stdout(process::Stdio::inherit())
-        .stderr(process::Stdio::inherit())
+    let mut run_cmd = match memcheck_backend {
+        Some(build_sys::MemcheckBackend::Valgrind) => {
+            let argv = build_sys::wrap_with_valgrind(&bin_path, &[]);
+            let mut cmd = process::Command::new(&argv[0]);
+            cmd.args(&argv[1..]);
+            cmd
+        }
+        Some(build_sys::MemcheckBackend::Sanitizer) => process::Command::new(&bin_path),
+        None => process::Command::new(&bin_path),
+    };
+    run_cmd
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::inherit());
+    set_dylib_search_path(&mut run_cmd, &so_dir);
+
+    let run_output = run_cmd
         .output()
-        .map_err(|e| anyhow!("Failed to run {:?} binary: {}", bin_path, e))?;
+        .with_context(|| format!("Failed to run {:?} binary", bin_path))?;
 
-    fs::remove_file(bin_path)?;
-    
-    Ok(())
+    fs::remove_file(&bin_path).ok();
+
+    if memcheck_backend == Some(build_sys::MemcheckBackend::Valgrind)
+        && run_output.status.code() == Some(build_sys::VALGRIND_ERROR_EXIT_CODE)
+    {
+        return Err(anyhow!("valgrind detected a memory error"));
+    }
+
+    let stdout = String::from_utf8_lossy(&run_output.stdout).to_string();
+
+    let observation = harness::TestObservation {
+        compiled: true,
+        compile_stderr: observation.compile_stderr,
+        exit_code: run_output.status.code(),
+        stdout: stdout.clone(),
+    };
+
+    if let Err(msg) = harness::check_expectation(&directives, &observation) {
+        return Err(anyhow!("{}", msg));
+    }
+
+    if let Some(expected_path) = &directives.expected_stdout_file {
+        if bless {
+            fs::write(expected_path, &stdout)?;
+            return Ok(format!("ok (blessed `{}`)", expected_path));
+        }
+
+        let expected = fs::read_to_string(expected_path)
+            .with_context(|| format!("couldn't read expected-output file `{}`", expected_path))?;
+
+        if expected != stdout {
+            let diff = harness::diff_lines(&expected, &stdout);
+            return Err(anyhow!(
+                "stdout did not match `{}`:\n{}",
+                expected_path,
+                diff.join("\n")
+            ));
+        }
+    }
+
+    let backend_note = match memcheck_backend {
+        Some(build_sys::MemcheckBackend::Valgrind) => " (memcheck: valgrind)",
+        Some(build_sys::MemcheckBackend::Sanitizer) => " (memcheck: sanitizer)",
+        None => "",
+    };
+    Ok(format!("ok{}", backend_note))
+}
+
+/// Runs a single test, turning its `handle_tests` result into a `TestOutcome`
+/// that records pass/fail and wall-clock duration for the consolidated summary.
+fn run_single_test(config: &Config, test_file: &str, bless: bool, memcheck: bool) -> TestOutcome {
+    let start = time::Instant::now();
+    let result = handle_tests("--debug", config, test_file, bless, memcheck);
+    let duration = start.elapsed();
+
+    match result {
+        Ok(message) => TestOutcome {
+            name: test_file.to_string(),
+            passed: true,
+            message,
+            duration,
+        },
+        Err(err) => TestOutcome {
+            name: test_file.to_string(),
+            passed: false,
+            message: err.to_string(),
+            duration,
+        },
+    }
+}
+
+/// Drives `files` through `run_single_test` with up to `jobs` running concurrently.
+/// `main` is already a tokio runtime, so each test's (blocking) compile+run is
+/// dispatched via `spawn_blocking` and bounded by a semaphore sized to `jobs`.
+async fn run_test_suite(
+    config: &Config,
+    files: Vec<String>,
+    bless: bool,
+    memcheck: bool,
+    jobs: usize,
+) -> Vec<TestOutcome> {
+    let config = std::sync::Arc::new(config.clone());
+    let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(jobs.max(1)));
+
+    let mut handles = Vec::with_capacity(files.len());
+    for file in files {
+        let config = config.clone();
+        let semaphore = semaphore.clone();
+        handles.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await.unwrap();
+            tokio::task::spawn_blocking(move || run_single_test(&config, &file, bless, memcheck))
+                .await
+                .unwrap()
+        }));
+    }
+
+    let mut outcomes = Vec::with_capacity(handles.len());
+    for handle in handles {
+        outcomes.push(handle.await.unwrap());
+    }
+    outcomes
 }
\ No newline at end of file
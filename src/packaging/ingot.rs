@@ -25,11 +25,83 @@ impl IngotMetadata {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metadata {
-    // Some ingots will only have code, and some may only have precompiled static libraries. 
-    // These fields tell us which is which. 
+    // Some ingots will only have code, and some may only have precompiled static libraries.
+    // These fields tell us which is which.
     pub source_support: bool,
     pub staticlib_support: bool,
-    pub sys_libs: Vec<String>,
+    /// Whether this ingot ships a prebuilt, versioned shared library (see
+    /// [`SharedLib`]) alongside (or instead of) a static one.
+    #[serde(default)]
+    pub dynamiclib_support: bool,
+    #[serde(default)]
+    pub shared_lib: Option<SharedLib>,
+    /// Header filenames (relative to this ingot's own directory) an umbrella
+    /// header was generated from. See [`crate::header_gen::gen_umbrella_header`].
+    #[serde(default)]
+    pub exported_headers: Vec<String>,
+    pub sys_libs: Vec<NativeLib>,
     pub ingot_deps: Vec<KilnIngot>,
 }
 
+/// The filenames a published shared library is known under, so a dependent
+/// ingot links against the exact file the publishing build produced instead
+/// of guessing `lib<name>.so`. Populated by
+/// [`crate::build_sys::ProjBuilder::build_dylib`] from its
+/// [`crate::target::SharedLibNaming`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedLib {
+    /// The real file the build produced, e.g. `libfoo.so.1.2.3`.
+    pub versioned_filename: String,
+    /// The name a dependent ingot should link against, e.g. `libfoo.so.1`.
+    /// `None` on Windows, which links the import library by name instead.
+    pub soname: Option<String>,
+    /// The Windows import library sitting next to the `.dll`, e.g. `foo.lib`.
+    pub import_lib_filename: Option<String>,
+}
+
+/// How a declared `sys_libs` entry should be linked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NativeLibKind {
+    /// Resolved through `pkg-config`, e.g. `"openssl >= 1.1"`. The default,
+    /// matching a plain string entry.
+    Dynamic,
+    /// Linked statically by exact file name, e.g. `-l:libfoo.a`.
+    Static,
+    /// Like `Static`, but pulled in whole (`-Wl,--whole-archive`, or
+    /// `-Wl,-force_load` on macOS) so objects no symbol directly references
+    /// aren't dropped by the linker.
+    StaticWholeArchive,
+    /// A macOS framework, linked with `-framework <name>`.
+    Framework,
+    /// Passed to the linker exactly as written, with no `-l`/extension handling.
+    Verbatim,
+}
+
+/// A single `sys_libs` entry. A plain string (`"openssl"`) deserializes as
+/// [`NativeLibKind::Dynamic`]; the explicit table form (`{ kind = "static",
+/// name = "foo" }`) selects one of the other link modes. Declaration order is
+/// preserved end to end, since native static-library link order is load-bearing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NativeLib {
+    Name(String),
+    Explicit { kind: NativeLibKind, name: String },
+}
+
+impl NativeLib {
+    pub fn kind(&self) -> NativeLibKind {
+        match self {
+            NativeLib::Name(_) => NativeLibKind::Dynamic,
+            NativeLib::Explicit { kind, .. } => *kind,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            NativeLib::Name(name) => name,
+            NativeLib::Explicit { name, .. } => name,
+        }
+    }
+}
+
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use super::package_manager::PkgError;
+
+/// The name of the marker file `install_globally` drops inside a package's global
+/// directory, so `check_pkgs` can tell whether on-disk contents still match the
+/// `Kiln.lock` record without re-downloading or re-hashing the unpacked tree.
+pub const INTEGRITY_MARKER: &str = ".kiln-integrity";
+
+/// A single resolved package's record in `Kiln.lock`: the tag that was selected,
+/// the tarball URL it was fetched from, and an SSRI-style integrity string
+/// (`sha512-<base64>`) computed over the raw tarball bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockEntry {
+    pub tag: String,
+    pub tarball_url: String,
+    pub integrity: String,
+}
+
+/// `Kiln.lock`: one [`LockEntry`] per resolved package, keyed by `host/owner/repo`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub package: HashMap<String, LockEntry>,
+}
+
+impl Lockfile {
+    /// Loads `Kiln.lock` from `path`, or an empty lockfile if it doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self, PkgError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let s = fs::read_to_string(path)?;
+        toml::from_str(&s)
+            .map_err(|e| PkgError::Unknown(format!("Failed to parse Kiln.lock: {}", e)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), PkgError> {
+        let s = toml::to_string_pretty(self)?;
+        fs::write(path, s)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&LockEntry> {
+        self.package.get(key)
+    }
+
+    pub fn set(&mut self, key: String, entry: LockEntry) {
+        self.package.insert(key, entry);
+    }
+}
+
+/// The key a package is recorded under in `Kiln.lock`.
+pub fn lock_key(host: &str, owner: &str, repo_name: &str) -> String {
+    format!("{}/{}/{}", host, owner, repo_name)
+}
+
+/// Computes an npm-style SSRI integrity string over raw bytes.
+pub fn integrity_sha512(bytes: &[u8]) -> String {
+    let digest = Sha512::digest(bytes);
+    format!("sha512-{}", base64::engine::general_purpose::STANDARD.encode(digest))
+}
+
+/// Hex-encodes the same digest [`integrity_sha512`] uses, for use as a
+/// filesystem directory name in the content-addressed package store (the
+/// base64 SSRI string contains `/` and `+`, which aren't safe path components).
+pub fn content_address(bytes: &[u8]) -> String {
+    let digest = Sha512::digest(bytes);
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,7 @@
+pub mod ingot;
+pub mod kiln_package;
+pub mod lockfile;
+pub mod package_manager;
+pub mod publish;
+pub mod semver;
+pub mod source;
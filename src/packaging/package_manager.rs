@@ -1,11 +1,15 @@
 use crate::config::{self, Config, KilnIngot};
-use crate::constants::{CONFIG_FILE, PACKAGE_CONFIG_FILE};
+use crate::constants::{CONFIG_FILE, LOCKFILE, PACKAGE_CONFIG_FILE, PACKAGE_STORE_DIR, TAG_CACHE_DIR};
 use crate::packaging::ingot::IngotMetadata;
+use crate::packaging::lockfile::{self, Lockfile};
+use crate::packaging::semver;
+use crate::packaging::source::{self, PackageSource};
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::io::Write;
 use std::path::Path;
+use std::sync::Arc;
 use std::{fs, time::Duration};
 
 use flate2::read::GzDecoder;
@@ -13,10 +17,16 @@ use reqwest;
 use serde::{Deserialize, Serialize};
 use tar::Archive;
 use tempfile::TempDir;
+use tokio::sync::{Mutex, Semaphore};
 
 use anyhow;
 use thiserror::Error;
 
+/// How many repos `resolve_adding_package` will fetch/install at once. A bound
+/// rather than a hard sequential loop, so a BFS level with hundreds of
+/// transitive deps doesn't fire them all at the remote host simultaneously.
+const MAX_CONCURRENT_FETCHES: usize = 8;
+
 #[derive(Debug, Error)]
 pub enum PkgError {
     // =============== Crate Errors ===============
@@ -91,117 +101,266 @@ impl AsRef<Path> for DepType {
     }
 }
 
-pub fn parse_github_uri(uri: &str) -> Result<(&str, &str), PkgError> {
-    let mut uri = match uri.split_once("github.com/") {
-        Some(s) => s.1,
-        None => return Err(PkgError::UsrErr("Invalid GitHub uri".to_string())),
-    };
+/// Splits a dependency URI (`https://<host>/<owner>/<repo>[.git]`) into its
+/// host, owner, and repo name, so callers can dispatch to the right
+/// [`PackageSource`] without assuming every dependency lives on GitHub.
+pub fn parse_repo_uri(uri: &str) -> Result<(&str, &str, &str), PkgError> {
+    let rest = uri.split_once("://").map(|(_, r)| r).unwrap_or(uri);
 
-    if uri.ends_with(".com") || uri.ends_with(".git") {
-        uri = &uri[..uri.len() - 4];
-    }
+    let (host, path) = rest
+        .split_once('/')
+        .ok_or_else(|| PkgError::UsrErr(format!("Invalid dependency uri `{}`", uri)))?;
 
-    let (owner, proj_name) = match uri.split_once("/") {
-        Some(s) => s,
-        None => return Err(PkgError::UsrErr("Invalid GitHub uri".to_string())),
-    };
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let path = path.trim_end_matches('/');
+
+    let (owner, repo) = path
+        .split_once('/')
+        .ok_or_else(|| PkgError::UsrErr(format!("Invalid dependency uri `{}`", uri)))?;
+
+    Ok((host, owner, repo))
+}
 
-    Ok((owner, proj_name))
+/// A repo's cached tag list, keyed by `owner/repo` under [`TAG_CACHE_DIR`]. The
+/// `etag` lets subsequent calls send `If-None-Match` and skip re-downloading
+/// (and re-paginating) a list that hasn't changed since.
+#[derive(Debug, Serialize, Deserialize)]
+struct TagCacheEntry {
+    etag: Option<String>,
+    tags: Vec<Tag>,
 }
 
-async fn find_tags(owner: &str, repo_name: &str) -> Result<Vec<Tag>, PkgError> {
-    let endpoint = format!("https://api.github.com/repos/{}/{}/tags", owner, repo_name);
+fn tag_cache_path(owner: &str, repo_name: &str) -> std::path::PathBuf {
+    (*TAG_CACHE_DIR).join(format!("{}_{}.json", owner, repo_name))
+}
 
-    // println!("Endpoint: {}", endpoint);
-    // std::process::exit(0);
+fn load_tag_cache(owner: &str, repo_name: &str) -> Option<TagCacheEntry> {
+    let s = fs::read_to_string(tag_cache_path(owner, repo_name)).ok()?;
+    serde_json::from_str(&s).ok()
+}
 
-    let res = reqwest::ClientBuilder::new()
-        .timeout(Duration::from_secs(4))
-        .build()?
-        .get(&endpoint)
-        .header("User-Agent", "Kiln Build System")
-        .send()
-        .await?;
-
-    if !res.status().is_success() {
-        return Err(PkgError::Unknown(format!(
-            "Non 200 status code from github: {}",
-            res.status().as_str()
-        )));
+fn save_tag_cache(owner: &str, repo_name: &str, entry: &TagCacheEntry) -> Result<(), PkgError> {
+    fs::create_dir_all(&*TAG_CACHE_DIR)?;
+    let s = serde_json::to_string_pretty(entry)?;
+    fs::write(tag_cache_path(owner, repo_name), s)?;
+    Ok(())
+}
+
+/// Pulls the `<url>` out of a `rel="next"` entry in a `Link` response header,
+/// e.g. `<https://api.github.com/...&page=2>; rel="next", <...>; rel="last"`.
+fn parse_next_link(link_header: &str) -> Option<String> {
+    for part in link_header.split(',') {
+        let part = part.trim();
+        if !part.ends_with("rel=\"next\"") {
+            continue;
+        }
+        let start = part.find('<')?;
+        let end = part.find('>')?;
+        return Some(part[start + 1..end].to_string());
     }
+    None
+}
+
+pub(crate) async fn find_tags_github(owner: &str, repo_name: &str) -> Result<Vec<Tag>, PkgError> {
+    let client = reqwest::ClientBuilder::new()
+        .timeout(Duration::from_secs(4))
+        .build()?;
 
-    let body = res.text().await?;
+    let cached = load_tag_cache(owner, repo_name);
 
-    let tags: Vec<Tag> = serde_json::from_str(&body)?;
+    let mut endpoint = Some(format!(
+        "https://api.github.com/repos/{}/{}/tags?per_page=100",
+        owner, repo_name
+    ));
+    let mut first_page = true;
+    let mut etag = None;
     let mut packages = vec![];
-    for t in tags {
-        packages.push(t);
+
+    while let Some(url) = endpoint.take() {
+        let mut req = client.get(&url).header("User-Agent", "Kiln Build System");
+        if first_page {
+            if let Some(cached) = &cached {
+                if let Some(etag) = &cached.etag {
+                    req = req.header("If-None-Match", etag.clone());
+                }
+            }
+        }
+
+        let res = req.send().await?;
+
+        if first_page && res.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(cached.unwrap().tags);
+        }
+
+        if !res.status().is_success() {
+            return Err(PkgError::Unknown(format!(
+                "Non 200 status code from github: {}",
+                res.status().as_str()
+            )));
+        }
+
+        if first_page {
+            etag = res
+                .headers()
+                .get(reqwest::header::ETAG)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+        }
+
+        endpoint = res
+            .headers()
+            .get(reqwest::header::LINK)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_next_link);
+
+        let body = res.text().await?;
+        let tags: Vec<Tag> = serde_json::from_str(&body)?;
+        packages.extend(tags);
+
+        first_page = false;
     }
 
+    let _ = save_tag_cache(
+        owner,
+        repo_name,
+        &TagCacheEntry {
+            etag,
+            tags: packages.clone(),
+        },
+    );
+
     Ok(packages)
 }
 
 /// Installs a package in the glocal cache. does NOT create a kiln-package.toml file
-/// If the package already exists locally, it does nothing
-async fn install_globally(package: &KilnIngot, tag: &Tag) -> Result<(), PkgError> {
+/// If the package already exists locally, it does nothing.
+///
+/// Verifies the downloaded tarball's SSRI integrity against `lockfile` (recording a
+/// new entry the first time a package is resolved), so a mutated release or a
+/// MITM'd download is caught instead of silently unpacked.
+///
+/// The unpacked tree itself lives under [`PACKAGE_STORE_DIR`], keyed by the
+/// tarball's content hash rather than by `owner/repo/tag` — `package_dir` is
+/// just a link to that content-addressed directory. So a tag re-resolved under
+/// a new name, or a different repo vendoring a byte-identical release, reuses
+/// the already-unpacked tree instead of downloading and unpacking it again.
+async fn install_globally(
+    package: &KilnIngot,
+    tag: &Tag,
+    lockfile: &Arc<Mutex<Lockfile>>,
+    source: &dyn PackageSource,
+) -> Result<(), PkgError> {
     let package_dir = package.get_global_path();
-    let tarball_tmp_name = format!(
-        "{}_{}_{}",
-        &package.owner(),
-        &package.repo_name(),
-        &package.version
-    );
 
     if package_dir.exists() {
         return Ok(());
     }
-    fs::create_dir_all(&package_dir)?;
 
-    let res = reqwest::Client::new()
-        .get(tag.tarball_url.clone())
-        .header("User-Agent", "Kiln Build System")
-        .send();
+    let body = source.fetch_tarball(tag).await?;
+    let integrity = lockfile::integrity_sha512(&body);
+    let key = lockfile::lock_key(package.host(), package.owner(), package.repo_name());
+
+    {
+        let mut lock = lockfile.lock().await;
+        match lock.get(&key) {
+            Some(entry) if entry.integrity != integrity => {
+                return Err(PkgError::UsrErr(format!(
+                    "Integrity check failed for {}: Kiln.lock expects {}, but the downloaded tarball hashes to {}. \
+                    The release may have been mutated or tampered with in transit.",
+                    key, entry.integrity, integrity
+                )));
+            }
+            Some(_) => {}
+            None => {
+                lock.set(
+                    key,
+                    lockfile::LockEntry {
+                        tag: tag.name.clone(),
+                        tarball_url: tag.tarball_url.clone(),
+                        integrity: integrity.clone(),
+                    },
+                );
+            }
+        }
+    }
 
-    let res = tokio::spawn(res);
+    let content_dir = (*PACKAGE_STORE_DIR).join(lockfile::content_address(&body));
+    if !content_dir.exists() {
+        fs::create_dir_all(&content_dir)?;
+
+        // Anything short of a full, verified unpack must not leave `content_dir`
+        // behind: its mere existence is what the check above treats as "already
+        // fetched", so a half-finished unpack would otherwise be silently
+        // trusted (and never retried) on a later install sharing the same hash.
+        if let Err(e) = unpack_tarball(&body, &content_dir, &integrity) {
+            let _ = fs::remove_dir_all(&content_dir);
+            return Err(e);
+        }
+    }
 
-    // Create a temporary directory
-    let tmp_dir = TempDir::new()?;
-    let tmp_file = tmp_dir.path().join(format!("{}.tar.gz", tarball_tmp_name));
-
-    let res = res.await??;
-    if !res.status().is_success() {
-        let mut msg =
-            format!("Github returned a non 200 status code when trying to download the tarball\n");
-        msg.push_str(&format!("Status code: {}\n", res.status().as_u16()));
-        msg.push_str(&format!(
-            "Text: \n{}\n",
-            res.text().await.unwrap_or("".to_string())
-        ));
-
-        return Err(PkgError::Unknown(msg));
+    if let Some(parent) = package_dir.parent() {
+        fs::create_dir_all(parent)?;
     }
+    link_dir(&content_dir, &package_dir)?;
 
-    let body = res.bytes().await?;
-    let body: Vec<u8> = body.to_vec();
+    Ok(())
+}
 
-    fs::write(&tmp_file, &body)?;
+fn unpack_tarball(body: &[u8], dest: &Path, integrity: &str) -> Result<(), PkgError> {
+    let tmp_dir = TempDir::new()?;
+    let tmp_file = tmp_dir.path().join("fetched.tar.gz");
+    fs::write(&tmp_file, body)?;
 
     let tar_gz = fs::File::open(&tmp_file)?;
     let tar = GzDecoder::new(tar_gz);
+    unpack_without_top_folder(tar, dest)?;
 
-    unpack_without_top_folder(tar, &package_dir)?;
+    fs::write(dest.join(lockfile::INTEGRITY_MARKER), integrity)?;
 
     Ok(())
 }
 
+/// Makes `link` resolve to `target`'s contents: a symlink on platforms that
+/// support one without elevated privileges, or a one-time recursive copy
+/// otherwise. Either way `link` behaves like an ordinary directory to every
+/// other caller of [`KilnIngot::get_global_path`].
+#[cfg(unix)]
+fn link_dir(target: &Path, link: &Path) -> Result<(), PkgError> {
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn link_dir(target: &Path, link: &Path) -> Result<(), PkgError> {
+    copy_dir_recursive(target, link)
+}
+
+#[cfg(not(unix))]
+fn copy_dir_recursive(src: &Path, dest: &Path) -> Result<(), PkgError> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let dest_path = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_recursive(&entry.path(), &dest_path)?;
+        } else {
+            fs::copy(entry.path(), dest_path)?;
+        }
+    }
+    Ok(())
+}
+
 /// Takes care of the entire installation process (High Level Function)
 /// PRECONDITION: CWD must be in the root directory of a kiln project
 /// This *will* take care of chained dependncies
 pub async fn resolve_adding_package(
     config: &mut config::Config,
+    host: &str,
     owner: &str,
     proj_name: &str,
     version: Option<&str>,
+    allow_build_scripts: bool,
+    offline: bool,
 ) -> Result<(), PkgError> {
     // TODO: Add a better error message by providing the link to see all the github repo's tags
     if let None = config.dependency {
@@ -210,88 +369,290 @@ pub async fn resolve_adding_package(
 
     let mut packages_added: HashSet<String> = HashSet::new();
 
-    let mut deps = vec![[
-        owner.to_string(),
-        proj_name.to_string(),
-        version.unwrap_or("").to_string(),
-    ]];
+    // Every constraint seen for a given repo across the whole BFS, so a diamond
+    // (A depends on lib@1.0, B depends on lib@2.0) is caught instead of silently
+    // resolved to whichever requirement was visited first.
+    let mut constraints: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    let mut tag_cache: HashMap<String, Vec<Tag>> = HashMap::new();
+    let mut repo_idents: HashMap<String, (String, String, String)> = HashMap::new();
+
+    // Repos reached directly by the user's `kiln add` (possibly alongside also
+    // being pulled in transitively); everything else is a transitive dependency
+    // and has its build/fetch scripts gated by `allow_build_scripts`.
+    let mut top_level: HashSet<String> = HashSet::new();
+
+    let lockfile_path = Path::new(LOCKFILE);
+    let lockfile = Arc::new(Mutex::new(Lockfile::load(lockfile_path)?));
+
+    // Bounds how many repos are fetched/installed at once: a BFS level can name
+    // hundreds of transitive deps, and firing them all at the remote host (or the
+    // local disk) simultaneously is more likely to trip rate limits than to
+    // finish any faster.
+    let fetch_permits = Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES));
+
+    let mut deps = vec![PendingDep {
+        host: host.to_string(),
+        owner: owner.to_string(),
+        proj_name: proj_name.to_string(),
+        version: version.map(str::to_string),
+        requirer: "(requested)".to_string(),
+    }];
 
     while deps.len() > 0 {
         let mut futures = vec![];
 
         for dep in &deps {
-            let owner = dep[0].clone();
-            let proj_name = dep[1].clone();
-            let version = if dep[2] == "" {
-                None
-            } else {
-                Some(dep[2].clone())
-            };
+            let repo_key = lockfile::lock_key(&dep.host, &dep.owner, &dep.proj_name);
+            constraints
+                .entry(repo_key.clone())
+                .or_default()
+                .push((dep.requirer.clone(), dep.version.clone().unwrap_or_default()));
+            repo_idents
+                .entry(repo_key.clone())
+                .or_insert_with(|| (dep.host.clone(), dep.owner.clone(), dep.proj_name.clone()));
+            if dep.requirer == "(requested)" {
+                top_level.insert(repo_key.clone());
+            }
 
-            let repo_name = format!("https://github.com/{}/{}", owner, proj_name);
-            if packages_added.contains(&repo_name) {
+            if packages_added.contains(&repo_key) {
                 continue;
             }
-            packages_added.insert(repo_name);
-
-            let f = add_package(owner, proj_name, version);
+            packages_added.insert(repo_key.clone());
+
+            let permits = fetch_permits.clone();
+            let host = dep.host.clone();
+            let owner = dep.owner.clone();
+            let proj_name = dep.proj_name.clone();
+            let version = dep.version.clone();
+            let lockfile = lockfile.clone();
+            let f = async move {
+                let _permit = permits.acquire_owned().await.unwrap();
+                add_package(host, owner, proj_name, version, lockfile, offline).await
+            };
             let f = tokio::spawn(f);
-            futures.push(f);
+            futures.push((repo_key, f));
         }
         deps.clear();
 
-        for f in futures {
-            let (chain_deps, cfg) = f.await??;
+        for (repo_key, f) in futures {
+            let (chain_deps, cfg, tags) = f.await??;
+            tag_cache.insert(repo_key.clone(), tags);
+
+            if !allow_build_scripts && !top_level.contains(&repo_key) && has_build_scripts(&cfg) {
+                return Err(PkgError::UsrErr(format!(
+                    "{} is a transitive dependency that declares a [scripts] pre-build or \
+                    post-fetch hook. Refusing to resolve it, since a deep dependency shouldn't \
+                    be able to run arbitrary code on a routine `kiln add`. Pass \
+                    --allow-build-scripts if you trust it.",
+                    repo_key
+                )));
+            }
+
             let kiln_dcf_deps = config.dependency.as_mut().unwrap();
             config::KilnIngot::add_dependency(kiln_dcf_deps, cfg);
-            deps.extend(chain_deps);
+
+            for (chain_host, chain_owner, chain_proj, chain_rev) in chain_deps {
+                deps.push(PendingDep {
+                    host: chain_host,
+                    owner: chain_owner,
+                    proj_name: chain_proj,
+                    version: if chain_rev.is_empty() { None } else { Some(chain_rev) },
+                    requirer: repo_key.clone(),
+                });
+            }
         }
     }
 
+    // Reconcile every repo that was requested more than once: find a single tag
+    // satisfying every accumulated constraint, re-installing it if it differs
+    // from whichever one was picked while the BFS was still discovering the tree.
+    for (repo_key, reqs) in &constraints {
+        if reqs.len() < 2 {
+            continue;
+        }
+
+        let mut comparators = vec![];
+        for (_, constraint) in reqs {
+            if constraint.is_empty() {
+                continue;
+            }
+            comparators.extend(semver::parse_constraint(constraint)?);
+        }
+        if comparators.is_empty() {
+            continue;
+        }
+
+        let (host, owner, proj_name) = &repo_idents[repo_key];
+        let Some(tags) = tag_cache.get(repo_key) else {
+            continue;
+        };
+
+        let satisfying = tags
+            .iter()
+            .filter_map(|t| semver::Version::parse(&t.name).map(|v| (t, v)))
+            .filter(|(_, v)| semver::satisfies(v, &comparators))
+            .max_by(|(_, a), (_, b)| a.cmp(b));
+
+        let Some((best_tag, _)) = satisfying else {
+            let mut msg = format!("Conflicting version requirements for {}/{}:\n", owner, proj_name);
+            for (requirer, constraint) in reqs {
+                let constraint = if constraint.is_empty() { "(any)" } else { constraint.as_str() };
+                msg.push_str(&format!("  {} requires {}\n", requirer, constraint));
+            }
+            return Err(PkgError::UsrErr(msg));
+        };
+
+        let kiln_dcf_deps = config.dependency.as_ref().unwrap();
+        let already_resolved = kiln_dcf_deps
+            .iter()
+            .any(|d| d.owner() == owner && d.repo_name() == proj_name && d.rev() == Some(best_tag.name.as_str()));
+
+        if !already_resolved {
+            let pkg = KilnIngot::new_with_host(host, owner, proj_name, &best_tag.name);
+            let source = source::source_for(host);
+            install_globally(&pkg, best_tag, &lockfile, source.as_ref()).await?;
+
+            let kiln_dcf_deps = config.dependency.as_mut().unwrap();
+            kiln_dcf_deps.retain(|d| !(d.owner() == owner && d.repo_name() == proj_name));
+            config::KilnIngot::add_dependency(kiln_dcf_deps, pkg);
+        }
+    }
+
+    lockfile.lock().await.save(lockfile_path)?;
+
     Ok(())
 }
 
+/// Whether `cfg` declares any `[scripts]` hook that would run code outside of
+/// the normal compiler invocation.
+fn has_build_scripts(cfg: &config::Config) -> bool {
+    cfg.scripts
+        .as_ref()
+        .map_or(false, |s| s.pre_build.is_some() || s.post_fetch.is_some())
+}
+
+/// One not-yet-resolved dependency edge discovered during the BFS in
+/// [`resolve_adding_package`]: `requirer` is `"(requested)"` for the top-level
+/// `kiln add` target, or the `host/owner/repo` lock key of the package whose
+/// manifest named it.
+struct PendingDep {
+    host: String,
+    owner: String,
+    proj_name: String,
+    version: Option<String>,
+    requirer: String,
+}
+
+/// The versions of `host/owner/repo` already present in the global cache, read
+/// straight off disk as `PACKAGE_DIR/<host>/<owner>/<repo>/<rev>`. Used by
+/// `offline` resolution, which must never touch the network.
+fn cached_versions(host: &str, owner: &str, proj_name: &str) -> Vec<Tag> {
+    let dir = (*crate::constants::PACKAGE_DIR).join(host).join(owner).join(proj_name);
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .map(|name| Tag {
+            name,
+            zipball_url: String::new(),
+            tarball_url: String::new(),
+        })
+        .collect()
+}
+
+/// Looks up `host/owner/proj_name`'s `Kiln.lock` entry and returns it as a `Tag`
+/// if it's pinned to exactly `version`. `version` is `None` for a fresh, explicit
+/// `kiln add` (which should always re-resolve against the live tag list so the
+/// user actually gets an upgrade) and `Some(exact rev)` for a transitive
+/// dependency or a reinstall of an already-resolved one — in both of those
+/// cases the pinned tarball can be installed directly, with no need to re-list
+/// and re-pick from the host's full tag set.
+async fn locked_tag(
+    lockfile: &Arc<Mutex<Lockfile>>,
+    host: &str,
+    owner: &str,
+    proj_name: &str,
+    version: Option<&str>,
+) -> Option<Tag> {
+    let version = version?;
+    let key = lockfile::lock_key(host, owner, proj_name);
+    let lock = lockfile.lock().await;
+    let entry = lock.get(&key)?;
+    if entry.tag != version {
+        return None;
+    }
+    Some(Tag {
+        name: entry.tag.clone(),
+        zipball_url: String::new(),
+        tarball_url: entry.tarball_url.clone(),
+    })
+}
+
 /// Takes care of the remote to global to local instalation process
 /// This pseudo-recursive helper function to [fn resolve_adding_package]
 async fn add_package(
+    host: String,
     owner: String,
     proj_name: String,
     version: Option<String>,
-) -> Result<(Vec<[String; 3]>, KilnIngot), PkgError> {
+    lockfile: Arc<Mutex<Lockfile>>,
+    offline: bool,
+) -> Result<(Vec<(String, String, String, String)>, KilnIngot, Vec<Tag>), PkgError> {
     // TODO: Add a better error message by providing the link to see all the github repo's tags
-    let repo_name = format!("https://github.com/{}/{}", owner, proj_name);
-
-    let tags = find_tags(&owner, &proj_name).await?;
-    if tags.len() == 0 {
-        return Err(PkgError::UsrErr(format!(
-            "No versions available for {}",
-            repo_name
-        )));
-    }
-
-    let mut tag: &Tag = &tags[0];
-    if let Some(v) = version {
-        let mut assigned = false;
-        for t in &tags {
-            if t.name == v {
-                tag = t;
-                assigned = true;
-            }
-        }
-        if !assigned {
-            let msg = format!(
-                "Version {} does not exist for https:://{}/{}",
-                v, owner, proj_name
-            );
-            return Err(PkgError::UsrErr(msg));
+    let repo_name = format!("{}/{}/{}", host, owner, proj_name);
+
+    let (tags, pkg) = if offline {
+        let tags = cached_versions(&host, &owner, &proj_name);
+        if tags.is_empty() {
+            return Err(PkgError::UsrErr(format!(
+                "{} is not present in the global cache and --offline was passed; \
+                run `kiln add` without --offline once to fetch it.",
+                repo_name
+            )));
         }
+
+        let tag = semver::select_best(&tags, version.as_deref().unwrap_or("*")).map_err(|_| {
+            PkgError::UsrErr(format!(
+                "No cached version of {} satisfies `{}`; run `kiln add` without --offline to fetch it.",
+                repo_name,
+                version.as_deref().unwrap_or("*")
+            ))
+        })?;
+        let pkg = KilnIngot::new_with_host(&host, &owner, &proj_name, &tag.name);
+        (tags.clone(), pkg)
+    } else if let Some(locked) = locked_tag(&lockfile, &host, &owner, &proj_name, version.as_deref()).await {
+        // `version` names an exact rev already pinned in `Kiln.lock` (a transitive
+        // dependency, or a reinstall of an existing `Kiln.toml` entry) — install it
+        // directly from the recorded tarball URL instead of re-querying the host for
+        // its full tag list just to pick the same version back out again.
+        let pkg = KilnIngot::new_with_host(&host, &owner, &proj_name, &locked.name);
+        let source = source::source_for(&host);
+        install_globally(&pkg, &locked, &lockfile, source.as_ref()).await?;
+        (vec![locked], pkg)
     } else {
-        tag = tags.last().unwrap();
-    }
+        let source = source::source_for(&host);
+
+        let tags = source.list_versions(&owner, &proj_name).await?;
+        if tags.len() == 0 {
+            return Err(PkgError::UsrErr(format!(
+                "No versions available for {}",
+                repo_name
+            )));
+        }
 
-    let pkg = KilnIngot::new(&owner, &proj_name, &tag.name);
+        let tag: &Tag = match &version {
+            Some(v) => semver::select_best(&tags, v)?,
+            None => tags.last().unwrap(),
+        };
 
-    install_globally(&pkg, &tag).await?;
+        let pkg = KilnIngot::new_with_host(&host, &owner, &proj_name, &tag.name);
+        install_globally(&pkg, tag, &lockfile, source.as_ref()).await?;
+        (tags, pkg)
+    };
 
     let mut chain_dep_ids = vec![];
 
@@ -301,29 +662,30 @@ async fn add_package(
         }
         let chain_deps = cfg.dependency.as_ref().unwrap();
         for chain_dep in chain_deps {
-            let (chain_owner, chain_repo) = parse_github_uri(&chain_dep.uri)?;
-
-            chain_dep_ids.push([
-                chain_owner.to_string(),
-                chain_repo.to_string(),
-                chain_dep.version.clone(),
-            ]);
+            chain_dep_ids.push((
+                chain_dep.host().to_string(),
+                chain_dep.owner().to_string(),
+                chain_dep.repo_name().to_string(),
+                chain_dep.rev().unwrap_or("").to_string(),
+            ));
         }
     }
 
-    Ok((chain_dep_ids, pkg))
+    Ok((chain_dep_ids, pkg, tags))
 }
 
 /// Ensures that all the packages listed in the Kiln.toml config file are
-/// all installed globally. Any that are listed but are not installed will be
-/// returned
-pub fn check_pkgs<'a>(config: &'a Config) -> Vec<[String; 3]> {
+/// all installed globally. Any that are listed but are not installed, or whose
+/// on-disk contents no longer match their `Kiln.lock` integrity record, will be
+/// returned.
+pub fn check_pkgs<'a>(config: &'a Config) -> Vec<[String; 4]> {
     let mut not_installed = vec![];
     let mut pkgs_visited: HashSet<String> = HashSet::new();
+    let lockfile = Lockfile::load(Path::new(LOCKFILE)).unwrap_or_default();
 
     if let Some(deps) = &config.dependency {
         for dep in deps {
-            check_pkg_h(dep, &mut not_installed, &mut pkgs_visited);
+            check_pkg_h(dep, &mut not_installed, &mut pkgs_visited, &lockfile);
         }
     }
 
@@ -332,19 +694,31 @@ pub fn check_pkgs<'a>(config: &'a Config) -> Vec<[String; 3]> {
 
 fn check_pkg_h(
     dep: &KilnIngot,
-    output: &mut Vec<[String; 3]>,
+    output: &mut Vec<[String; 4]>,
     pkgs_visited: &mut HashSet<String>,
+    lockfile: &Lockfile,
 ) {
-    if pkgs_visited.contains(dep.uri.as_str()) {
+    let dep_key = lockfile::lock_key(dep.host(), dep.owner(), dep.repo_name());
+    if pkgs_visited.contains(&dep_key) {
         return;
     }
-    pkgs_visited.insert(dep.uri.clone());
+    pkgs_visited.insert(dep_key.clone());
+
+    let global_path = dep.get_global_path();
+    let needs_reinstall = !global_path.exists() || match lockfile.get(&dep_key) {
+        Some(entry) => match fs::read_to_string(global_path.join(lockfile::INTEGRITY_MARKER)) {
+            Ok(marker) => marker.trim() != entry.integrity,
+            Err(_) => true,
+        },
+        None => false,
+    };
 
-    if !dep.get_global_path().exists() {
+    if needs_reinstall {
         let pkg = [
+            dep.host().to_string(),
             dep.owner().to_string(),
             dep.repo_name().to_string(),
-            dep.version.clone(),
+            dep.rev().unwrap_or("").to_string(),
         ];
 
         if !output.contains(&pkg) {
@@ -356,7 +730,7 @@ fn check_pkg_h(
     if let Some(kiln_cfg) = dep.get_kiln_cfg().unwrap() {
         if let Some(chain_deps) = &kiln_cfg.dependency {
             for chain_dep in chain_deps {
-                check_pkg_h(chain_dep, output, pkgs_visited);
+                check_pkg_h(chain_dep, output, pkgs_visited, lockfile);
             }
         }
     }
@@ -0,0 +1,136 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use anyhow::{anyhow, Result};
+use flate2::{write::GzEncoder, Compression};
+use tar::{Builder, Header};
+use tempfile::TempDir;
+
+use crate::config::Config;
+use crate::constants::CONFIG_FILE;
+
+/// Normalized so that two packaging runs of identical source produce a byte-identical
+/// archive (cargo package does the same for its `.crate` tarballs).
+const REPRODUCIBLE_MODE: u32 = 0o644;
+const REPRODUCIBLE_MTIME: u64 = 0;
+
+/// Collects every file that would ship inside the ingot tarball: the project
+/// manifest, every `src_dir`, and every `include_dir`.
+pub fn collect_package_files(config: &Config) -> Result<Vec<PathBuf>> {
+    let mut files = vec![PathBuf::from(CONFIG_FILE)];
+
+    for dir in config.project.src_dir.iter().chain(&config.project.include_dir) {
+        let dir = Path::new(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Writes `files` into a gzip-compressed tar archive at `dest`, normalizing mode and
+/// mtime on every entry so the resulting bytes are reproducible across machines.
+fn write_tarball(files: &[PathBuf], dest: &Path) -> Result<()> {
+    let tar_gz = fs::File::create(dest)?;
+    let enc = GzEncoder::new(tar_gz, Compression::default());
+    let mut archive = Builder::new(enc);
+
+    for file in files {
+        let data = fs::read(file)?;
+
+        let mut header = Header::new_gnu();
+        header.set_path(file)?;
+        header.set_size(data.len() as u64);
+        header.set_mode(REPRODUCIBLE_MODE);
+        header.set_mtime(REPRODUCIBLE_MTIME);
+        header.set_cksum();
+
+        archive.append(&header, data.as_slice())?;
+    }
+
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Copies `files` into a fresh temp directory and runs the compiler with
+/// `-fsyntax-only` over the copied `.c` sources, to confirm the packaged set
+/// compiles on its own (nothing is linked or written out).
+fn verify_tarball_builds(config: &Config, files: &[PathBuf]) -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+
+    for file in files {
+        let dest = tmp_dir.path().join(file);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(file, dest)?;
+    }
+
+    let mut sources = vec![];
+    for dir in &config.project.src_dir {
+        let src_dir = tmp_dir.path().join(dir);
+        if !src_dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&src_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("c") {
+                sources.push(path);
+            }
+        }
+    }
+
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    let compiler = config.get_compiler_path();
+    let status = process::Command::new(&compiler)
+        .arg("-fsyntax-only")
+        .args(&sources)
+        .current_dir(tmp_dir.path())
+        .status()?;
+
+    if !status.success() {
+        return Err(anyhow!(
+            "packaged ingot failed a clean build verification pass"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Builds a verified, reproducible `.tar.gz` ingot archive at
+/// `<name>-<version>.tar.gz` in the project root.
+pub fn package_ingot(config: &Config, list_only: bool) -> Result<Option<PathBuf>> {
+    let files = collect_package_files(config)?;
+
+    if list_only {
+        for file in &files {
+            println!("{}", file.display());
+        }
+        return Ok(None);
+    }
+
+    verify_tarball_builds(config, &files)?;
+
+    let archive_name = format!("{}-{}.tar.gz", config.project.name, config.project.version);
+    let dest = PathBuf::from(&archive_name);
+    write_tarball(&files, &dest)?;
+
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "Packaged {} files into {}", files.len(), archive_name)?;
+
+    Ok(Some(dest))
+}
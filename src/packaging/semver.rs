@@ -0,0 +1,250 @@
+use std::cmp::Ordering;
+
+use super::package_manager::{PkgError, Tag};
+
+/// A parsed `major.minor.patch[-prerelease]` version, as found in a GitHub tag name
+/// (with an optional leading `v` already stripped).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub prerelease: Option<String>,
+}
+
+impl Version {
+    /// Parses `v1.2.3`, `1.2`, or `1.2.3-rc.1`, defaulting missing `minor`/`patch`
+    /// fields to `0`.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.strip_prefix('v').unwrap_or(input);
+        let (core, prerelease) = match input.split_once('-') {
+            Some((c, p)) => (c, Some(p.to_string())),
+            None => (input, None),
+        };
+
+        let mut parts = core.splitn(3, '.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().unwrap_or("0").parse().ok()?;
+        let patch = parts.next().unwrap_or("0").parse().ok()?;
+
+        Some(Version { major, minor, patch, prerelease })
+    }
+
+    fn numeric(&self) -> (u64, u64, u64) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+/// Numeric fields order first; a prerelease always sorts below the release of
+/// the same numeric version (`1.0.0-rc.1 < 1.0.0`).
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numeric().cmp(&other.numeric()).then_with(|| {
+            match (&self.prerelease, &other.prerelease) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            }
+        })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+/// One half of a constraint, e.g. the `>=1.2.3` out of `^1.2.3`'s expansion.
+#[derive(Debug, Clone)]
+pub struct Comparator {
+    op: Op,
+    version: Version,
+}
+
+impl Comparator {
+    fn matches(&self, v: &Version) -> bool {
+        match self.op {
+            Op::Eq => v == &self.version,
+            Op::Gt => v > &self.version,
+            Op::Gte => v >= &self.version,
+            Op::Lt => v < &self.version,
+            Op::Lte => v <= &self.version,
+        }
+    }
+}
+
+fn caret_range(v: Version) -> Vec<Comparator> {
+    let upper = Version { major: v.major + 1, minor: 0, patch: 0, prerelease: None };
+    vec![
+        Comparator { op: Op::Gte, version: v },
+        Comparator { op: Op::Lt, version: upper },
+    ]
+}
+
+fn tilde_range(v: Version) -> Vec<Comparator> {
+    let upper = Version { major: v.major, minor: v.minor + 1, patch: 0, prerelease: None };
+    vec![
+        Comparator { op: Op::Gte, version: v },
+        Comparator { op: Op::Lt, version: upper },
+    ]
+}
+
+fn wildcard_range(major: u64, minor: Option<u64>) -> Vec<Comparator> {
+    let (lower, upper) = match minor {
+        Some(minor) => (
+            Version { major, minor, patch: 0, prerelease: None },
+            Version { major, minor: minor + 1, patch: 0, prerelease: None },
+        ),
+        None => (
+            Version { major, minor: 0, patch: 0, prerelease: None },
+            Version { major: major + 1, minor: 0, patch: 0, prerelease: None },
+        ),
+    };
+    vec![
+        Comparator { op: Op::Gte, version: lower },
+        Comparator { op: Op::Lt, version: upper },
+    ]
+}
+
+fn parse_version(input: &str) -> Result<Version, PkgError> {
+    Version::parse(input).ok_or_else(|| PkgError::UsrErr(format!("Invalid version `{}`", input)))
+}
+
+fn parse_clause(clause: &str) -> Result<Vec<Comparator>, PkgError> {
+    let clause = clause.trim();
+
+    if clause == "*" {
+        return Ok(vec![]);
+    }
+    if let Some(rest) = clause.strip_prefix('^') {
+        return Ok(caret_range(parse_version(rest)?));
+    }
+    if let Some(rest) = clause.strip_prefix('~') {
+        return Ok(tilde_range(parse_version(rest)?));
+    }
+    for (prefix, op) in [(">=", Op::Gte), ("<=", Op::Lte), (">", Op::Gt), ("<", Op::Lt), ("=", Op::Eq)] {
+        if let Some(rest) = clause.strip_prefix(prefix) {
+            return Ok(vec![Comparator { op, version: parse_version(rest.trim())? }]);
+        }
+    }
+    if clause.contains(['x', 'X', '*']) {
+        let parts: Vec<&str> = clause.split('.').collect();
+        let major = parts
+            .first()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| PkgError::UsrErr(format!("Invalid version constraint `{}`", clause)))?;
+        let minor = parts.get(1).and_then(|s| s.parse().ok());
+        return Ok(wildcard_range(major, minor));
+    }
+
+    // A bare version, e.g. `1.2`, is treated the same as `^1.2`.
+    Ok(caret_range(parse_version(clause)?))
+}
+
+/// Parses a comma-separated constraint (`^1.2.3`, `~1.4`, `>=1.0, <2.0`, `1.x`)
+/// into a set of comparators that must all match (logical AND).
+pub fn parse_constraint(input: &str) -> Result<Vec<Comparator>, PkgError> {
+    let mut comparators = vec![];
+    for clause in input.split(',') {
+        comparators.extend(parse_clause(clause)?);
+    }
+    Ok(comparators)
+}
+
+pub fn satisfies(version: &Version, comparators: &[Comparator]) -> bool {
+    comparators.iter().all(|c| c.matches(version))
+}
+
+/// Among `tags` satisfying `constraint`, returns the highest-precedence one.
+/// Prerelease tags are excluded unless `constraint` itself names a prerelease.
+/// Tags whose name isn't valid semver are skipped rather than treated as an error,
+/// since some repos mix release tags with unrelated ones.
+///
+/// `constraint` accepts the same requirement operators Cargo does: `^1.2`
+/// (compatible updates), `~1.2.3` (patch-only updates), comparison operators
+/// (`>=1.0, <2.0`, comma-separated as logical AND), `1.x`/`1.2.x` wildcards, and
+/// a bare version (treated as `^version`). This replaces ranking tags by plain
+/// string comparison, which ranks `v0.10.0` below `v0.9.0` and can't express "any
+/// compatible version" at all.
+pub fn select_best<'a>(tags: &'a [Tag], constraint: &str) -> Result<&'a Tag, PkgError> {
+    let comparators = parse_constraint(constraint)?;
+    let allow_prerelease = comparators.iter().any(|c| c.version.prerelease.is_some());
+
+    let mut best: Option<(&Tag, Version)> = None;
+    for tag in tags {
+        let Some(v) = Version::parse(&tag.name) else {
+            continue;
+        };
+        if v.prerelease.is_some() && !allow_prerelease {
+            continue;
+        }
+        if !satisfies(&v, &comparators) {
+            continue;
+        }
+        if best.as_ref().map_or(true, |(_, best_v)| v > *best_v) {
+            best = Some((tag, v));
+        }
+    }
+
+    best.map(|(tag, _)| tag).ok_or_else(|| {
+        let available: Vec<&str> = tags.iter().map(|t| t.name.as_str()).collect();
+        let mut msg = format!("No version satisfying `{}` found.", constraint);
+
+        match closest_tag(constraint, tags) {
+            Some(closest) => msg.push_str(&format!(" Did you mean \"{}\"?", closest)),
+            None => msg.push_str(&format!(" Available tags: {}", available.join(", "))),
+        }
+
+        PkgError::UsrErr(msg)
+    })
+}
+
+/// The Levenshtein edit distance between `a` and `b` (single-char insert/delete/
+/// substitute cost), computed with the standard rolling-row dynamic-programming
+/// table — no need to keep more than the previous row in memory at a time.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut prev_row: Vec<usize> = (0..=b.len()).collect();
+    let mut curr_row = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        curr_row[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            curr_row[j] = (prev_row[j] + 1)
+                .min(curr_row[j - 1] + 1)
+                .min(prev_row[j - 1] + cost);
+        }
+        std::mem::swap(&mut prev_row, &mut curr_row);
+    }
+
+    prev_row[b.len()]
+}
+
+/// Finds the tag name closest (by edit distance) to a requested-but-missing
+/// `version`, for a "did you mean" hint — the same typo-suggestion idea Cargo
+/// uses for mistyped subcommands (`lev_distance`), applied to version strings.
+/// Only suggests a match within a third of the requested string's length (and
+/// never less than one edit), so an unrelated tag isn't offered as a "did you mean".
+fn closest_tag<'a>(version: &str, tags: &'a [Tag]) -> Option<&'a str> {
+    let threshold = (version.len() / 3).max(1);
+
+    tags.iter()
+        .map(|t| (t.name.as_str(), levenshtein(version, &t.name)))
+        .filter(|&(_, dist)| dist <= threshold)
+        .min_by_key(|&(_, dist)| dist)
+        .map(|(name, _)| name)
+}
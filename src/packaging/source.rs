@@ -0,0 +1,273 @@
+use std::process::Command;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tempfile::TempDir;
+
+use super::package_manager::{self, PkgError, Tag};
+
+/// Prefix stashed onto a [`Tag::tarball_url`] to mark it as a plain git remote
+/// rather than a downloadable archive, so [`HttpSource::fetch_tarball`] knows to
+/// clone it instead of issuing an HTTP GET.
+const GIT_REMOTE_PREFIX: &str = "git+";
+
+/// Fetches the available versions of, and tarballs for, a dependency hosted at
+/// some provider. [`source_for`] picks the right implementation from the host
+/// parsed out of a dependency's `git` URL, so [`super::package_manager::add_package`]
+/// doesn't need to know about GitHub/GitLab/registry specifics itself.
+#[async_trait]
+pub trait PackageSource: Send + Sync {
+    async fn list_versions(&self, owner: &str, repo: &str) -> Result<Vec<Tag>, PkgError>;
+    async fn fetch_tarball(&self, tag: &Tag) -> Result<Vec<u8>, PkgError>;
+}
+
+/// Picks the `PackageSource` implementation for a dependency URI's `host` (as
+/// returned by [`package_manager::parse_repo_uri`]). Anything that isn't a
+/// recognized host (e.g. `github.com`, `gitlab.com`) is treated as a generic
+/// HTTP registry reachable at that host.
+pub fn source_for(host: &str) -> Box<dyn PackageSource> {
+    match host {
+        "github.com" => Box::new(GitHubSource),
+        "gitlab.com" => Box::new(GitLabSource),
+        other => Box::new(HttpSource {
+            registry_host: other.to_string(),
+        }),
+    }
+}
+
+async fn download_bytes(url: &str) -> Result<Vec<u8>, PkgError> {
+    let res = reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", "Kiln Build System")
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        return Err(PkgError::Unknown(format!(
+            "Non 200 status code ({}) fetching {}",
+            res.status().as_u16(),
+            url
+        )));
+    }
+
+    Ok(res.bytes().await?.to_vec())
+}
+
+/// The original, and default, source: GitHub's REST API for tags, and each
+/// tag's own `tarball_url` for the archive.
+pub struct GitHubSource;
+
+#[async_trait]
+impl PackageSource for GitHubSource {
+    async fn list_versions(&self, owner: &str, repo: &str) -> Result<Vec<Tag>, PkgError> {
+        package_manager::find_tags_github(owner, repo).await
+    }
+
+    async fn fetch_tarball(&self, tag: &Tag) -> Result<Vec<u8>, PkgError> {
+        download_bytes(&tag.tarball_url).await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabTag {
+    name: String,
+}
+
+/// GitLab's REST API v4 for tags, and its `/-/archive/` endpoint for the
+/// corresponding tarball.
+pub struct GitLabSource;
+
+#[async_trait]
+impl PackageSource for GitLabSource {
+    async fn list_versions(&self, owner: &str, repo: &str) -> Result<Vec<Tag>, PkgError> {
+        let project_path = format!("{}/{}", owner, repo).replace('/', "%2F");
+        let endpoint = format!(
+            "https://gitlab.com/api/v4/projects/{}/repository/tags",
+            project_path
+        );
+
+        let res = reqwest::Client::new()
+            .get(&endpoint)
+            .header("User-Agent", "Kiln Build System")
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(PkgError::Unknown(format!(
+                "Non 200 status code from gitlab: {}",
+                res.status().as_str()
+            )));
+        }
+
+        let body = res.text().await?;
+        let tags: Vec<GitLabTag> = serde_json::from_str(&body)?;
+
+        Ok(tags
+            .into_iter()
+            .map(|t| {
+                let archive_url = format!(
+                    "https://gitlab.com/{}/{}/-/archive/{}/{}-{}.tar.gz",
+                    owner, repo, t.name, repo, t.name
+                );
+                Tag {
+                    name: t.name,
+                    zipball_url: archive_url.clone(),
+                    tarball_url: archive_url,
+                }
+            })
+            .collect())
+    }
+
+    async fn fetch_tarball(&self, tag: &Tag) -> Result<Vec<u8>, PkgError> {
+        download_bytes(&tag.tarball_url).await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryVersion {
+    version: String,
+    tarball_url: String,
+}
+
+/// A generic HTTP registry: `GET https://<host>/<owner>/<repo>/index.json`
+/// returns the published versions and their tarball URLs, in the spirit of
+/// Cargo's sparse registry protocol.
+///
+/// Most self-hosted forges (a bare `git` daemon, sr.ht, a company's internal
+/// Gitea) don't speak this protocol at all, so `list_versions` falls back to
+/// `git ls-remote --tags` against `https://<host>/<owner>/<repo>.git` whenever
+/// the registry index isn't there, and `fetch_tarball` clones and checks out
+/// the tag instead of downloading an archive that was never published.
+pub struct HttpSource {
+    registry_host: String,
+}
+
+#[async_trait]
+impl PackageSource for HttpSource {
+    async fn list_versions(&self, owner: &str, repo: &str) -> Result<Vec<Tag>, PkgError> {
+        let endpoint = format!("https://{}/{}/{}/index.json", self.registry_host, owner, repo);
+
+        if let Ok(body) = download_bytes(&endpoint).await {
+            if let Ok(versions) = serde_json::from_slice::<Vec<RegistryVersion>>(&body) {
+                return Ok(versions
+                    .into_iter()
+                    .map(|v| Tag {
+                        name: v.version,
+                        zipball_url: v.tarball_url.clone(),
+                        tarball_url: v.tarball_url,
+                    })
+                    .collect());
+            }
+        }
+
+        let remote = format!("https://{}/{}/{}.git", self.registry_host, owner, repo);
+        list_git_tags(&remote)
+    }
+
+    async fn fetch_tarball(&self, tag: &Tag) -> Result<Vec<u8>, PkgError> {
+        match tag.tarball_url.strip_prefix(GIT_REMOTE_PREFIX) {
+            Some(remote) => clone_and_archive(remote, &tag.name),
+            None => download_bytes(&tag.tarball_url).await,
+        }
+    }
+}
+
+/// Lists a plain git remote's tags via `git ls-remote --tags`, tagging each
+/// resulting [`Tag::tarball_url`] with [`GIT_REMOTE_PREFIX`] so `fetch_tarball`
+/// knows to clone `remote` rather than download an archive.
+fn list_git_tags(remote: &str) -> Result<Vec<Tag>, PkgError> {
+    let output = Command::new("git")
+        .args(["ls-remote", "--tags", remote])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(PkgError::Unknown(format!(
+            "`git ls-remote --tags {}` failed with {}",
+            remote, output.status
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let marked_remote = format!("{}{}", GIT_REMOTE_PREFIX, remote);
+
+    Ok(stdout
+        .lines()
+        .filter_map(|line| line.split_once("refs/tags/"))
+        .map(|(_, name)| name)
+        // A peeled `^{}` entry duplicates the tag it annotates; skip it.
+        .filter(|name| !name.ends_with("^{}"))
+        .map(|name| Tag {
+            name: name.to_string(),
+            zipball_url: marked_remote.clone(),
+            tarball_url: marked_remote.clone(),
+        })
+        .collect())
+}
+
+/// Clones `remote` into a temp dir, checks out `rev`, and archives the result
+/// as an in-memory `tar.gz` so callers can treat it exactly like a downloaded
+/// release tarball.
+///
+/// Submodules are initialized after checkout, and the archive is made with
+/// `tar` from the checked-out working tree (every `.git` dir excluded), so a
+/// dependency that vendors submodules ships their contents too.
+fn clone_and_archive(remote: &str, rev: &str) -> Result<Vec<u8>, PkgError> {
+    let tmp_dir = TempDir::new()?;
+    let repo_dir = tmp_dir.path().join("repo");
+
+    let clone = Command::new("git")
+        .args(["clone", "--quiet", remote])
+        .arg(&repo_dir)
+        .status()?;
+    if !clone.success() {
+        return Err(PkgError::Unknown(format!(
+            "`git clone` of {} failed with {}",
+            remote, clone
+        )));
+    }
+
+    let checkout = Command::new("git")
+        .args(["checkout", "--quiet", rev])
+        .current_dir(&repo_dir)
+        .status()?;
+    if !checkout.success() {
+        return Err(PkgError::Unknown(format!(
+            "`git checkout {}` failed in {:?}",
+            rev, repo_dir
+        )));
+    }
+
+    let submodules = Command::new("git")
+        .args(["submodule", "update", "--init", "--recursive"])
+        .current_dir(&repo_dir)
+        .status()?;
+    if !submodules.success() {
+        return Err(PkgError::Unknown(format!(
+            "`git submodule update` failed in {:?}",
+            repo_dir
+        )));
+    }
+
+    // `git archive` packages the commit tree, not the working directory, so
+    // it would emit an empty dir for each submodule gitlink regardless of the
+    // `submodule update` above. Tar the actual checked-out tree instead,
+    // which does have submodule contents on disk, excluding `.git` dirs (the
+    // superproject's and every submodule's) since those aren't part of a
+    // release tarball.
+    let archive_path = tmp_dir.path().join("archive.tar.gz");
+    let archive = Command::new("tar")
+        .args(["--exclude=.git", "-czf"])
+        .arg(&archive_path)
+        .arg("-C")
+        .arg(&repo_dir)
+        .arg(".")
+        .status()?;
+    if !archive.success() {
+        return Err(PkgError::Unknown(format!(
+            "`tar` of {} at {} failed with {}",
+            remote, rev, archive
+        )));
+    }
+
+    Ok(std::fs::read(archive_path)?)
+}
@@ -0,0 +1,175 @@
+use std::path::Path;
+
+use crate::packaging::semver::Version;
+
+/// The operating-system half of a target triple, used to pick file extensions
+/// and compiler flags independent of the host kiln itself was built on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetOs {
+    Linux,
+    MacOs,
+    Windows,
+}
+
+impl TargetOs {
+    fn from_triple(triple: &str) -> Self {
+        if triple.contains("windows") {
+            TargetOs::Windows
+        } else if triple.contains("apple") || triple.contains("darwin") {
+            TargetOs::MacOs
+        } else {
+            TargetOs::Linux
+        }
+    }
+
+    fn host() -> Self {
+        if cfg!(target_os = "windows") {
+            TargetOs::Windows
+        } else if cfg!(target_os = "macos") {
+            TargetOs::MacOs
+        } else {
+            TargetOs::Linux
+        }
+    }
+}
+
+/// The platform kiln is building for: a triple (e.g. `x86_64-unknown-linux-gnu`)
+/// plus the OS it implies.
+///
+/// This replaces `STATIC_LIB_FE`/`DYNAMIC_LIB_FE`/`EXECUTABLE_FE`, which were
+/// resolved at compile time via `#[cfg(target_os)]` and so always described the
+/// machine kiln itself was built on, making cross-compilation impossible: every
+/// extension here is a function of the *requested* target, not the host.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub triple: String,
+    os: TargetOs,
+}
+
+/// What [`Target::shared_lib_naming`] says a versioned shared library build
+/// needs: the real filename the compiler should produce, the linker flags
+/// that stamp it, the `(link_name, target_name)` symlinks to create next to
+/// it afterward, and the name (if any) a dependent ingot should link against.
+#[derive(Debug, Clone)]
+pub struct SharedLibNaming {
+    pub versioned_filename: String,
+    pub link_flags: Vec<String>,
+    pub symlinks: Vec<(String, String)>,
+    pub soname: Option<String>,
+    pub import_lib_filename: Option<String>,
+}
+
+impl Target {
+    /// Builds a `Target` from an explicit `--target <triple>` value.
+    pub fn from_triple(triple: &str) -> Self {
+        Target {
+            triple: triple.to_string(),
+            os: TargetOs::from_triple(triple),
+        }
+    }
+
+    /// Reproduces kiln's old host-only behavior: no `--target` was passed, so
+    /// build for whatever OS kiln itself was compiled for.
+    pub fn default_host() -> Self {
+        Target {
+            triple: "host".to_string(),
+            os: TargetOs::host(),
+        }
+    }
+
+    pub fn static_lib_ext(&self) -> &'static str {
+        match self.os {
+            TargetOs::Windows => ".lib",
+            TargetOs::MacOs | TargetOs::Linux => ".a",
+        }
+    }
+
+    pub fn dynamic_lib_ext(&self) -> &'static str {
+        match self.os {
+            TargetOs::Windows => ".dll",
+            TargetOs::MacOs => ".dylib",
+            TargetOs::Linux => ".so",
+        }
+    }
+
+    pub fn executable_ext(&self) -> &'static str {
+        match self.os {
+            TargetOs::Windows => ".exe",
+            TargetOs::MacOs | TargetOs::Linux => "",
+        }
+    }
+
+    /// The filenames, linker flags, and post-link symlinks needed to produce a
+    /// properly versioned shared library named `name` (no `lib` prefix or
+    /// extension) at `version` for this target: a real `.so.MAJOR.MINOR.PATCH`
+    /// with a `-soname` on Linux, a `MAJOR.MINOR.PATCH.dylib` with
+    /// `-install_name`/`-current_version`/`-compatibility_version` on macOS, or
+    /// a plain `.dll` plus an import `.lib` on Windows (which has no soname
+    /// equivalent and needs no symlinks).
+    pub fn shared_lib_naming(&self, name: &str, version: &Version) -> SharedLibNaming {
+        match self.os {
+            TargetOs::Linux => {
+                let base = format!("lib{}.so", name);
+                let versioned = format!("{}.{}.{}.{}", base, version.major, version.minor, version.patch);
+                let soname = format!("{}.{}", base, version.major);
+                SharedLibNaming {
+                    versioned_filename: versioned.clone(),
+                    link_flags: vec![format!("-Wl,-soname,{}", soname)],
+                    symlinks: vec![(soname.clone(), versioned.clone()), (base, versioned)],
+                    soname: Some(soname),
+                    import_lib_filename: None,
+                }
+            }
+            TargetOs::MacOs => {
+                let stem = format!("lib{}", name);
+                let compat_name = format!("{}.{}.dylib", stem, version.major);
+                let versioned = format!("{}.{}.{}.{}.dylib", stem, version.major, version.minor, version.patch);
+                SharedLibNaming {
+                    versioned_filename: versioned.clone(),
+                    link_flags: vec![
+                        format!("-Wl,-install_name,@rpath/{}", compat_name),
+                        format!("-Wl,-current_version,{}.{}.{}", version.major, version.minor, version.patch),
+                        format!("-Wl,-compatibility_version,{}.0.0", version.major),
+                    ],
+                    symlinks: vec![(compat_name.clone(), versioned.clone()), (format!("{}.dylib", stem), versioned)],
+                    soname: Some(compat_name),
+                    import_lib_filename: None,
+                }
+            }
+            TargetOs::Windows => {
+                let dll = format!("{}.dll", name);
+                let implib = format!("{}.lib", name);
+                SharedLibNaming {
+                    versioned_filename: dll,
+                    link_flags: vec![format!("-Wl,--out-implib,{}", implib)],
+                    symlinks: vec![],
+                    soname: None,
+                    import_lib_filename: Some(implib),
+                }
+            }
+        }
+    }
+
+    /// The flag(s) that tell `compiler` to cross-compile for `self.triple`:
+    /// `-target <triple>` for clang, `--target <triple>` for nvcc. Cross gcc
+    /// is selected by binary name instead (e.g. `aarch64-linux-gnu-gcc`), so
+    /// there's no flag to add there; the host target needs no flag at all.
+    pub fn compiler_flags(&self, compiler: &str) -> Vec<String> {
+        if self.triple == "host" {
+            return vec![];
+        }
+
+        let bin = Path::new(compiler)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or(compiler);
+
+        if bin.contains("clang") {
+            vec!["-target".to_string(), self.triple.clone()]
+        } else if bin.contains("nvcc") {
+            vec!["--target".to_string(), self.triple.clone()]
+        } else {
+            vec![]
+        }
+    }
+}
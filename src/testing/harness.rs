@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::Path;
+
+/// What a test file is expected to do, declared via a leading `// kiln-mode: <mode>`
+/// comment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestMode {
+    /// The default: compile and run, expecting a zero exit code.
+    RunPass,
+    /// Compile and run, expecting a non-zero exit code.
+    RunFail,
+    /// Expect the compile step itself to fail.
+    CompileFail,
+}
+
+impl TestMode {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "run-pass" => Some(TestMode::RunPass),
+            "run-fail" => Some(TestMode::RunFail),
+            "compile-fail" => Some(TestMode::CompileFail),
+            _ => None,
+        }
+    }
+}
+
+/// The expectations scraped from a test file's `// kiln-*:` directive comments.
+#[derive(Debug, Clone, Default)]
+pub struct TestDirectives {
+    pub mode: Option<TestMode>,
+    /// Extra flags to pass to the compiler, e.g. from `// kiln-flags: -DFOO`.
+    pub flags: Vec<String>,
+    /// Path to a golden-output file to diff the binary's stdout against.
+    pub expected_stdout_file: Option<String>,
+    pub expected_exit: Option<i32>,
+    /// Substrings the compiler's stderr must contain (`compile-fail` only); repeatable.
+    pub expected_stderr_contains: Vec<String>,
+}
+
+impl TestDirectives {
+    pub fn mode(&self) -> TestMode {
+        self.mode.unwrap_or(TestMode::RunPass)
+    }
+}
+
+/// Scans the contiguous `// kiln-<key>: <value>` comment block at the top of a test
+/// file. Scanning stops at the first blank or non-comment line. Recognized keys:
+/// `kiln-mode` (`run-pass`/`run-fail`/`compile-fail`), `kiln-flags` (extra compiler
+/// flags, whitespace-separated), `kiln-expected-stdout` (path to a golden-output
+/// file), `kiln-expected-exit` (exact exit code), and `kiln-expected-stderr-contains`
+/// (a substring the compiler's stderr must contain; may appear more than once).
+/// Unknown keys are warned about and otherwise ignored.
+pub fn parse_directives(source: &str) -> TestDirectives {
+    let mut directives = TestDirectives::default();
+
+    for line in source.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if !line.starts_with("//") {
+            break;
+        }
+
+        let comment = line.trim_start_matches('/').trim();
+        let Some((key, value)) = comment.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim();
+
+        match key {
+            "kiln-mode" => match TestMode::parse(value) {
+                Some(mode) => directives.mode = Some(mode),
+                None => eprintln!("warning: unrecognized kiln-mode `{}`", value),
+            },
+            "kiln-flags" => directives
+                .flags
+                .extend(value.split_whitespace().map(str::to_string)),
+            "kiln-expected-stdout" => directives.expected_stdout_file = Some(value.to_string()),
+            "kiln-expected-exit" => match value.parse() {
+                Ok(code) => directives.expected_exit = Some(code),
+                Err(_) => eprintln!("warning: kiln-expected-exit `{}` is not an integer", value),
+            },
+            "kiln-expected-stderr-contains" => directives
+                .expected_stderr_contains
+                .push(value.to_string()),
+            _ => eprintln!("warning: unrecognized test directive `{}`", key),
+        }
+    }
+
+    directives
+}
+
+pub fn parse_directives_from_file(path: impl AsRef<Path>) -> std::io::Result<TestDirectives> {
+    let source = fs::read_to_string(path)?;
+    Ok(parse_directives(&source))
+}
+
+/// The observed result of running a test file through the build + execute pipeline.
+#[derive(Debug, Default)]
+pub struct TestObservation {
+    pub compiled: bool,
+    pub compile_stderr: String,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+}
+
+/// Compares what actually happened against the declared `TestDirectives` (exit
+/// status and compile-fail stderr substrings only; stdout-file diffing is handled
+/// separately by `diff_lines` since it needs filesystem access for `--bless`).
+pub fn check_expectation(directives: &TestDirectives, observed: &TestObservation) -> Result<(), String> {
+    match directives.mode() {
+        TestMode::CompileFail => {
+            if observed.compiled {
+                return Err("expected compilation to fail, but it succeeded".to_string());
+            }
+            for substr in &directives.expected_stderr_contains {
+                if !observed.compile_stderr.contains(substr.as_str()) {
+                    return Err(format!(
+                        "compiler stderr did not contain expected substring {:?}",
+                        substr
+                    ));
+                }
+            }
+            return Ok(());
+        }
+        TestMode::RunPass => {
+            if !observed.compiled {
+                return Err(format!(
+                    "expected compilation to succeed, but it failed:\n{}",
+                    observed.compile_stderr.trim()
+                ));
+            }
+            if observed.exit_code != Some(0) {
+                return Err(format!(
+                    "expected a zero exit code, got {:?}",
+                    observed.exit_code
+                ));
+            }
+        }
+        TestMode::RunFail => {
+            if !observed.compiled {
+                return Err(format!(
+                    "expected compilation to succeed, but it failed:\n{}",
+                    observed.compile_stderr.trim()
+                ));
+            }
+            if observed.exit_code == Some(0) {
+                return Err("expected a non-zero exit code, got 0".to_string());
+            }
+        }
+    }
+
+    if let Some(expected_exit) = directives.expected_exit {
+        if observed.exit_code != Some(expected_exit) {
+            return Err(format!(
+                "expected exit code {}, got {:?}",
+                expected_exit, observed.exit_code
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// A classic LCS (longest-common-subsequence) dynamic-programming line diff, walked
+/// to emit `-`/`+`/`  ` prefixed lines the way a unified diff would, minus hunk
+/// headers. Good enough for readable test-failure output without pulling in a diff
+/// crate.
+pub fn diff_lines(expected: &str, actual: &str) -> Vec<String> {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let n = expected.len();
+    let m = actual.len();
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if expected[i] == actual[j] {
+            diff.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    while i < n {
+        diff.push(format!("- {}", expected[i]));
+        i += 1;
+    }
+    while j < m {
+        diff.push(format!("+ {}", actual[j]));
+        j += 1;
+    }
+
+    diff
+}
@@ -1,12 +1,11 @@
-use crate::{constants::VALGRIND_OUT, lexer_c, utils};
+use crate::config::{Config, Severity};
+use crate::header_gen::lexer_c;
 
 use anyhow::{anyhow, Result};
 use std::{
     collections::HashMap,
     env,
-    fmt::Debug,
     fs,
-    process::{self, Command},
     sync::{Arc, Mutex},
 };
 
@@ -46,9 +45,36 @@ impl FunctionMap {
     }
 }
 
-#[derive(Debug)]
+/// The rule IDs consulted against a `kiln.toml` `[static_analysis]` table, e.g.
+/// `[static_analysis.format-string] enabled = false`.
+pub const RULE_UNSAFE_FUNCTION: &str = "unsafe-function";
+pub const RULE_FORMAT_STRING: &str = "format-string";
+pub const RULE_FIXED_BUFFER: &str = "fixed-buffer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WarningType {
     UnsafeFunction,
+    FormatString,
+    FixedBuffer,
+}
+
+impl WarningType {
+    fn rule_id(&self) -> &'static str {
+        match self {
+            WarningType::UnsafeFunction => RULE_UNSAFE_FUNCTION,
+            WarningType::FormatString => RULE_FORMAT_STRING,
+            WarningType::FixedBuffer => RULE_FIXED_BUFFER,
+        }
+    }
+
+    /// The severity a rule fires at when `kiln.toml` doesn't say otherwise.
+    fn default_severity(&self) -> Severity {
+        match self {
+            WarningType::UnsafeFunction => Severity::Warning,
+            WarningType::FormatString => Severity::Error,
+            WarningType::FixedBuffer => Severity::Error,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -57,9 +83,10 @@ pub struct Warning {
     pub filename: String,
     pub line: usize,
     pub warning_type: WarningType,
+    pub severity: Severity,
 }
 
-pub fn check_files(source_type: &str) -> Result<Vec<Warning>> {
+pub fn check_files(config: &Config, source_type: &str) -> Result<Vec<Warning>> {
     let mut warnings = vec![];
     let mut source_dir = env::current_dir()?;
     source_dir.push("src");
@@ -81,18 +108,32 @@ pub fn check_files(source_type: &str) -> Result<Vec<Warning>> {
             }
 
             let source_code = fs::read_to_string(path)?;
-            let mut curr_warnings = scan_file(&name, &source_code, &func_map);
+            let mut curr_warnings = scan_file(config, &name, &source_code, &func_map);
 
             warnings.append(&mut curr_warnings);
         }
     }
 
+    if warnings.iter().any(|w| w.severity == Severity::Error) {
+        return Err(anyhow!(
+            "{} error-level static analysis warning(s) found",
+            warnings
+                .iter()
+                .filter(|w| w.severity == Severity::Error)
+                .count()
+        ));
+    }
+
     Ok(warnings)
 }
 
 #[allow(unused)]
-pub fn check_files_threaded(source_type: &str, warn_buff: Arc<Mutex<Vec<Warning>>>) -> Result<()> {
-    let mut warnings = check_files(source_type)?;
+pub fn check_files_threaded(
+    config: &Config,
+    source_type: &str,
+    warn_buff: Arc<Mutex<Vec<Warning>>>,
+) -> Result<()> {
+    let mut warnings = check_files(config, source_type)?;
 
     let mut lock = warn_buff.lock().unwrap();
     lock.append(&mut warnings);
@@ -100,12 +141,54 @@ pub fn check_files_threaded(source_type: &str, warn_buff: Arc<Mutex<Vec<Warning>
     Ok(())
 }
 
-fn scan_file(filename: &str, source_code: &str, func_map: &FunctionMap) -> Vec<Warning> {
+fn scan_file(
+    config: &Config,
+    filename: &str,
+    source_code: &str,
+    func_map: &FunctionMap,
+) -> Vec<Warning> {
     let mut warnings = vec![];
 
-    let tokens = lexer_c::tokenize(source_code)
-        .unwrap();
+    let (tokens, _source_map) = lexer_c::tokenize(source_code).unwrap();
+
+    if config.rule_enabled(RULE_UNSAFE_FUNCTION, true) {
+        scan_unsafe_functions(config, filename, &tokens, func_map, &mut warnings);
+    }
+    if config.rule_enabled(RULE_FORMAT_STRING, true) {
+        scan_format_strings(config, filename, &tokens, &mut warnings);
+    }
+    if config.rule_enabled(RULE_FIXED_BUFFER, true) {
+        scan_fixed_buffers(config, filename, &tokens, &mut warnings);
+    }
+
+    warnings
+}
 
+fn push_warning(
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+    warning_type: WarningType,
+    filename: &str,
+    line: usize,
+    msg: String,
+) {
+    let severity = config.rule_severity(warning_type.rule_id(), warning_type.default_severity());
+    warnings.push(Warning {
+        msg,
+        filename: filename.to_string(),
+        line,
+        warning_type,
+        severity,
+    });
+}
+
+fn scan_unsafe_functions(
+    config: &Config,
+    filename: &str,
+    tokens: &[lexer_c::Token],
+    func_map: &FunctionMap,
+    warnings: &mut Vec<Warning>,
+) {
     for (token_num, token) in tokens.iter().enumerate() {
         if tokens[token_num..].len() < 3 {
             continue;
@@ -116,21 +199,166 @@ fn scan_file(filename: &str, source_code: &str, func_map: &FunctionMap) -> Vec<W
                 continue;
             }
             if let Some(safe_fn) = func_map.map.get(*obj) {
-                let warning = Warning {
-                    warning_type: WarningType::UnsafeFunction,
-                    msg: format!(
+                push_warning(
+                    config,
+                    warnings,
+                    WarningType::UnsafeFunction,
+                    filename,
+                    token_num + 1,
+                    format!(
                         "{}() is an unsafe function. Consuder using {}() instead",
                         obj, safe_fn
                     ),
-                    filename: filename.to_string(),
-                    line: token_num + 1,
-                };
+                );
+            }
+        }
+    }
+}
+
+/// Functions whose first call argument is a `printf`-style format string.
+const FORMAT_STRING_FUNCTIONS: &[&str] = &["printf"];
 
-                warnings.push(warning);
+/// Flags `printf(user_var, ...)` calls where the format argument is a bare
+/// identifier rather than a string literal, since a caller-controlled format
+/// string is a classic `%n`/format-string vulnerability.
+fn scan_format_strings(
+    config: &Config,
+    filename: &str,
+    tokens: &[lexer_c::Token],
+    warnings: &mut Vec<Warning>,
+) {
+    for (token_num, token) in tokens.iter().enumerate() {
+        let lexer_c::Token::Object(obj) = token else {
+            continue;
+        };
+        if !FORMAT_STRING_FUNCTIONS.contains(obj) {
+            continue;
+        }
+        if tokens.get(token_num + 1) != Some(&lexer_c::Token::OpenParen) {
+            continue;
+        }
+
+        let Some(arg_idx) = next_non_whitespace(tokens, token_num + 2) else {
+            continue;
+        };
+
+        if let lexer_c::Token::Object(arg) = tokens[arg_idx] {
+            push_warning(
+                config,
+                warnings,
+                WarningType::FormatString,
+                filename,
+                token_num + 1,
+                format!(
+                    "format string passed to {}() is not a literal (`{}`); this allows caller-controlled format specifiers",
+                    obj, arg
+                ),
+            );
+        }
+    }
+}
+
+/// Functions whose final argument is a byte count to copy into a fixed-size buffer.
+const FIXED_BUFFER_FUNCTIONS: &[&str] = &["strncpy", "memcpy"];
+
+/// Tracks `char buf[64];`-style stack array declarations and flags `strncpy`/`memcpy`
+/// calls into one of them whose literal size argument exceeds the declared capacity.
+fn scan_fixed_buffers(
+    config: &Config,
+    filename: &str,
+    tokens: &[lexer_c::Token],
+    warnings: &mut Vec<Warning>,
+) {
+    let mut array_sizes: HashMap<&str, usize> = HashMap::new();
+
+    for token_num in 0..tokens.len() {
+        if let lexer_c::Token::Object(name) = tokens[token_num] {
+            if tokens.get(token_num + 1) == Some(&lexer_c::Token::OpenSquareBracket) {
+                if let Some(lexer_c::Token::Number(size_str)) = tokens.get(token_num + 2) {
+                    if let Ok(size) = size_str.parse::<usize>() {
+                        array_sizes.insert(name, size);
+                    }
+                }
+            }
+        }
+
+        let lexer_c::Token::Object(obj) = tokens[token_num] else {
+            continue;
+        };
+        if !FIXED_BUFFER_FUNCTIONS.contains(&obj) {
+            continue;
+        }
+        if tokens.get(token_num + 1) != Some(&lexer_c::Token::OpenParen) {
+            continue;
+        }
+
+        let Some(dest_idx) = next_non_whitespace(tokens, token_num + 2) else {
+            continue;
+        };
+        let lexer_c::Token::Object(dest) = tokens[dest_idx] else {
+            continue;
+        };
+        let Some(&capacity) = array_sizes.get(dest) else {
+            continue;
+        };
+
+        let Some(size_idx) = find_call_last_arg(tokens, dest_idx) else {
+            continue;
+        };
+        let lexer_c::Token::Number(size_str) = tokens[size_idx] else {
+            continue;
+        };
+        let Ok(size) = size_str.parse::<usize>() else {
+            continue;
+        };
+
+        if size > capacity {
+            push_warning(
+                config,
+                warnings,
+                WarningType::FixedBuffer,
+                filename,
+                token_num + 1,
+                format!(
+                    "{}() copies {} bytes into `{}`, which was declared with only {} bytes of storage",
+                    obj, size, dest, capacity
+                ),
+            );
+        }
+    }
+}
+
+/// Walks forward from a call's first argument to the last top-level argument
+/// before the closing paren, skipping over nested parens.
+fn find_call_last_arg(tokens: &[lexer_c::Token], first_arg_idx: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut last_arg_idx = None;
+
+    for idx in first_arg_idx..tokens.len() {
+        match tokens[idx] {
+            lexer_c::Token::OpenParen => depth += 1,
+            lexer_c::Token::CloseParen => {
+                if depth == 0 {
+                    return last_arg_idx;
+                }
+                depth -= 1;
+            }
+            lexer_c::Token::Comma if depth == 0 => {
+                last_arg_idx = next_non_whitespace(tokens, idx + 1);
             }
+            _ => {}
         }
-        
     }
 
-    warnings
-}
\ No newline at end of file
+    None
+}
+
+fn next_non_whitespace(tokens: &[lexer_c::Token], mut idx: usize) -> Option<usize> {
+    while let Some(tok) = tokens.get(idx) {
+        match tok {
+            lexer_c::Token::Space | lexer_c::Token::Tab | lexer_c::Token::NewLine => idx += 1,
+            _ => return Some(idx),
+        }
+    }
+    None
+}
@@ -1,5 +1,3 @@
-use std::collections::HashSet;
-use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::{self, Command};
 
@@ -37,33 +35,6 @@ impl Language {
     }
 }
 
-/// Returns a vector of the included statements
-/// Ex) `["stdio.h", "<math.h>"]`
-pub fn extract_include_statements(path: &Path) -> Vec<String> {
-    let mut path = path.to_path_buf();
-    path.push("src");
-
-    let mut includes = HashSet::new();
-
-    for p in path.read_dir().unwrap() {
-        let p = p.unwrap();
-
-        let text = fs::read_to_string(p.path()).unwrap();
-
-        let local_include = text
-            .split("\n")
-            .map(|s| s.trim())
-            .filter(|s| s.starts_with("#include") && s.ends_with(">"))
-            .map(|s| format!("<{}", s.split_once("<").unwrap().1));
-
-        for inc in local_include {
-            includes.insert(inc);
-        }
-    }
-
-    includes.into_iter().collect()
-}
-
 #[allow(unused)]
 pub fn expand_user(path: &str) -> String {
     if path.starts_with("~/") {